@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use parser::parser::Parser;
+
+// Every input must parse into a value or an error, never panic.
+fuzz_target!(|data: &[u8]| {
+    let _ = Parser::new_with_comments().parse_slice(data);
+
+    if let Ok(source) = std::str::from_utf8(data) {
+        let mut parser = Parser::new_without_comments().max_depth(16);
+        let _ = parser.parse(source);
+        let _ = parser.parse_borrowed(source);
+        let _ = parser.parse_tape(source);
+    }
+});
@@ -9,7 +9,7 @@ fn main() {
     let s = "";
     println!("{}", s.len());
     let split_regex = Regex::new(NEWLINE_SPLIT_PATTERN).unwrap();
-    let substrings: Vec<_> = split_regex.split(s).into_iter().collect();
+    let substrings: Vec<_> = split_regex.split(s).collect();
     println!("{:#?}", substrings);
 
     let mut pos = Position::start();
@@ -27,7 +27,7 @@ fn main() {
 
     // let result = parser.parse(&" \n \n  // hello null\nnull\"$\"".to_string());
     // let result = parser.parse(&"\"$\"".to_string());
-    let result = parser.parse(&s.to_string());
+    let result = parser.parse_str(s);
     // let result = parser.parse(&" \n a".to_string());
     if let Err(err) = result {
         println!("Could not parse: {}", err);
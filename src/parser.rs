@@ -1,20 +1,50 @@
+pub mod access;
+pub mod borrowed;
+pub mod canonical;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod diff;
+pub mod eq;
+pub mod incremental;
+pub mod jsonpath;
+pub mod limits;
+pub mod locate;
+pub mod merge;
 pub mod node;
+pub mod patch;
+pub mod pointer;
+pub mod pull;
+pub mod sax;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
+pub mod tape;
 mod tokenizer;
+pub mod transform;
+#[cfg(feature = "serde_json")]
+pub mod value;
+pub mod visit;
 
-use std::collections::HashMap;
+use std::io::{ErrorKind, Read};
+use std::str::FromStr;
 
-use self::node::{
-    ArrayNode, BooleanNode, Node, NullNode, NumberNode, NumberNodeValue, ObjectNode, Span,
-    StringNode,
-};
-use self::tokenizer::{Token, TokenKind, Tokenizer};
+use self::limits::{byte_position, byte_position_after, Limits};
+use self::node::{BooleanNode, Node, NullNode, NumberNode, NumberNodeValue, Span, StringNode};
+use self::pull::{NodeBuilder, PullParser};
+use self::tokenizer::{Token, Tokenizer};
+
+/// How deeply arrays and objects may be nested by default, see
+/// [`Parser::max_depth`].
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// How many bytes [`Parser::parse_reader`] reads at a time.
+const READER_CHUNK_SIZE: usize = 8 * 1024;
 
 pub struct Parser<'source> {
     tokenizer: Tokenizer<'source>,
-    // tokens: Option<Vec<Token>>,
-    tokens: Vec<Token>,
-    index: usize,
-    source: Option<&'source String>,
+    deny_duplicate_keys: bool,
+    max_depth: usize,
+    limits: Limits,
 }
 
 impl<'source> Parser<'source> {
@@ -23,9 +53,9 @@ impl<'source> Parser<'source> {
     fn new(comments: bool) -> Parser<'source> {
         Parser {
             tokenizer: Tokenizer::new(comments),
-            tokens: vec![],
-            index: 0,
-            source: None,
+            deny_duplicate_keys: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            limits: Limits::default(),
         }
     }
 
@@ -37,193 +67,240 @@ impl<'source> Parser<'source> {
         Parser::new(true)
     }
 
-    fn reset(&mut self) {
-        self.tokens = vec![];
-        self.index = 0;
-        self.source = None;
+    /// Makes objects with duplicate keys an error. By default the last
+    /// member with a given key wins.
+    pub fn deny_duplicate_keys(mut self) -> Parser<'source> {
+        self.deny_duplicate_keys = true;
+        self
     }
 
-    // TODO: for the future: how do I communicate that this only returns
-    //       either Ok(Node::Array) or Ok(Node::Object)? I guess the only way is to doc it
-    pub fn parse(&mut self, source: &'source String) -> Result<Node, String> {
-        self.reset();
+    /// Limits how deeply arrays and objects may be nested, [`DEFAULT_MAX_DEPTH`]
+    /// by default. Parsing into a [`Node`] does not recurse, but dropping,
+    /// comparing or serializing it does (as does [`Parser::parse_borrowed`]),
+    /// so this keeps deeply nested input from overflowing the stack later on.
+    pub fn max_depth(mut self, depth: usize) -> Parser<'source> {
+        self.max_depth = depth;
+        self
+    }
 
-        self.source = Some(source);
+    /// Caps the resources parsing may take, see [`Limits`].
+    pub fn limits(mut self, limits: Limits) -> Parser<'source> {
+        self.tokenizer.set_limits(limits);
+        self.limits = limits;
+        self
+    }
 
-        self.tokens = self.tokenizer.tokenize(self.source.unwrap())?;
+    /// A parser with the same settings, for another source.
+    fn with_settings<'other>(&self) -> Parser<'other> {
+        let mut parser = Parser::new(self.tokenizer.comments()).limits(self.limits);
+        parser.deny_duplicate_keys = self.deny_duplicate_keys;
+        parser.max_depth = self.max_depth;
+        parser
+    }
 
-        match self.current().kind() {
-            TokenKind::OpenSquareBracket => self.parse_array_literal().map(Node::Array),
-            TokenKind::OpenCurlyBrace => self.parse_object_literal().map(Node::Object),
-            // TODO: obviously there has to be way more info here!
-            _ => Err(format!(
-                "unexpected token: must be array literal or object literal"
-            )),
-        }
+    /// Parses `source` into a single top-level value, see [`Parser::parse_str`].
+    pub fn parse(&mut self, source: &'source str) -> Result<Node, String> {
+        self.parse_str(source)
     }
 
-    fn parse_value(&mut self) -> Result<Node, String> {
-        match self.current().kind() {
-            TokenKind::NullLiteral => self.parse_null_literal().map(Node::Null),
-            TokenKind::BooleanLiteral => self.parse_boolean_literal().map(Node::Boolean),
-            TokenKind::NumberLiteral => self.parse_number_literal().map(Node::Number),
-            // FIXME: do this for all of these!
-            TokenKind::StringLiteral => self.parse_string_literal().map(Node::String),
-            TokenKind::OpenSquareBracket => self.parse_array_literal().map(Node::Array),
-            TokenKind::OpenCurlyBrace => self.parse_object_literal().map(Node::Object),
-            _ => panic!("this should not happens! tf?"),
-        }
+    /// Parses UTF-8 encoded `source`, see [`Parser::parse_str`].
+    pub fn parse_slice(&mut self, source: &'source [u8]) -> Result<Node, String> {
+        self.limits.check_input(source)?;
+        let source = std::str::from_utf8(source).map_err(|error| {
+            let (line, column) = byte_position(&source[..error.valid_up_to()]);
+            format!(
+                "invalid UTF-8 at byte {}, line {} col {}",
+                error.valid_up_to(),
+                line,
+                column + 1
+            )
+        })?;
+
+        self.parse_str(source)
     }
 
-    fn parse_object_literal(&mut self) -> Result<ObjectNode, String> {
-        let start = self.consume(TokenKind::OpenCurlyBrace)?.span().start();
-        let mut map = HashMap::new();
-
-        // TODO: See what happens with this if we don't terminate with a square bracket
-        while self.current().kind() != &TokenKind::ClosedCurlyBrace
-            && self.current().kind() != &TokenKind::Eoi
-        {
-            // let key = self.consume(TokenKind::StringLiteral)?;
-            // the below is easier since it's already removed the quotes
-            let key = self.parse_string_literal()?;
-            self.consume(TokenKind::Colon)?;
-            let value = self.parse_value()?;
-            // TODO: maybe handle if this doesn't return None? since we can't store dupes
-            map.insert(key.value, value);
-            if self.current().kind() != &TokenKind::ClosedCurlyBrace {
-                self.consume(TokenKind::Comma)?;
+    /// Parses UTF-8 encoded input from `reader`, see [`Parser::parse_slice`].
+    /// The input is read in chunks and parsed as it comes in, so only the
+    /// resulting tree and the last, unfinished token are kept in memory, and
+    /// reading stops as soon as the input is known to be malformed.
+    pub fn parse_reader<R: Read>(&mut self, mut reader: R) -> Result<Node, String> {
+        let mut parser = PullParser::new("", self.tokenizer.comments())
+            .max_depth(self.max_depth)
+            .limits(self.limits)
+            .suspend();
+        let mut builder = NodeBuilder::new(self.deny_duplicate_keys);
+
+        let mut chunk = vec![0; READER_CHUNK_SIZE];
+        // the input that has not been parsed yet
+        let mut source = String::new();
+        // read, but not decoded yet, e.g. a character split across chunks
+        let mut pending = Vec::new();
+        // the number of bytes decoded, and the line and column they end at
+        let mut decoded = 0;
+        let mut position = (1, 0);
+
+        loop {
+            let length = match reader.read(&mut chunk) {
+                Ok(length) => length,
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => return Err(format!("could not read source: {}", error)),
+            };
+            pending.extend_from_slice(&chunk[..length]);
+
+            if let Some(max) = self.limits.max_input_size {
+                if decoded + pending.len() > max {
+                    let (line, column) = byte_position_after(position, &pending[..max - decoded]);
+                    return Err(format!(
+                        "input longer than {} bytes at line {} col {}",
+                        max,
+                        line,
+                        column + 1
+                    ));
+                }
             }
-        }
 
-        let end = self.consume(TokenKind::ClosedCurlyBrace)?.span().end();
+            let valid = match std::str::from_utf8(&pending) {
+                Ok(valid) => valid,
+                // the rest may be the start of a character, unless the input ended
+                Err(error) if error.error_len().is_none() && length > 0 => {
+                    std::str::from_utf8(&pending[..error.valid_up_to()]).unwrap_or_default()
+                }
+                Err(error) => {
+                    let (line, column) =
+                        byte_position_after(position, &pending[..error.valid_up_to()]);
+                    return Err(format!(
+                        "invalid UTF-8 at byte {}, line {} col {}",
+                        decoded + error.valid_up_to(),
+                        line,
+                        column + 1
+                    ));
+                }
+            };
+            source.push_str(valid);
+            position = byte_position_after(position, valid.as_bytes());
+            decoded += valid.len();
+            pending.drain(..valid.len());
+
+            let start = parser.cursor();
+            let mut resumed = parser.resume(&source, length > 0);
+            while let Some(event) = resumed.next_raw()? {
+                builder.push(event)?;
+            }
+            if !resumed.is_starved() {
+                return builder
+                    .finish()
+                    .ok_or_else(|| "unexpected end of input".to_string());
+            }
 
-        Ok(ObjectNode {
-            span: Span::new(start, end),
-            value: map,
-            raw: self.source.unwrap()[start.cursor()..end.cursor()].to_string(),
-        })
+            let parsed = resumed.cursor() - start;
+            parser = resumed.suspend();
+            source.drain(..parsed);
+        }
     }
 
-    fn parse_array_literal(&mut self) -> Result<ArrayNode, String> {
-        let start = self.consume(TokenKind::OpenSquareBracket)?.span().start();
-        let mut array = Vec::new();
+    /// Parses `source` into a single top-level value (RFC 8259 allows any
+    /// value at the top level, not only arrays and objects).
+    pub fn parse_str(&mut self, source: &'source str) -> Result<Node, String> {
+        let mut events = PullParser::new(source, self.tokenizer.comments())
+            .max_depth(self.max_depth)
+            .limits(self.limits);
+        let mut builder = NodeBuilder::new(self.deny_duplicate_keys);
 
-        // TODO: See what happens with this if we don't terminate with a square bracket
-        while self.current().kind() != &TokenKind::ClosedSquareBracket
-            && self.current().kind() != &TokenKind::Eoi
-        {
-            let value = self.parse_value()?;
-            // FIXME: This only allows trailing commas, which is not compliant
-            array.push(value);
-            if self.current().kind() != &TokenKind::ClosedSquareBracket {
-                self.consume(TokenKind::Comma)?;
-            }
+        while let Some(event) = events.next_raw()? {
+            builder.push(event)?;
         }
 
-        let end = self.consume(TokenKind::ClosedSquareBracket)?.span().end();
-
-        Ok(ArrayNode {
-            span: Span::new(start, end),
-            value: array,
-            raw: self.source.unwrap()[start.cursor()..end.cursor()].to_string(),
-        })
+        builder
+            .finish()
+            .ok_or_else(|| "unexpected end of input".to_string())
     }
+}
 
-    fn parse_string_literal(&mut self) -> Result<StringNode, String> {
-        self.consume(TokenKind::StringLiteral).map(|token| {
-            let raw = token.raw();
-            StringNode {
-                raw: raw.to_string(),
-                // FIXME: Maybe extract this, and also think about if there are any edge-cases
-                value: raw[1..raw.len() - 1].to_string(),
-                span: *token.span(),
-            }
-        })
-    }
-
-    fn parse_number_literal(&mut self) -> Result<NumberNode, String> {
-        self.consume(TokenKind::NumberLiteral).map(|token| {
-            let raw = token.raw();
-            let value = if raw.contains(".") {
-                NumberNodeValue::Float(
-                    raw.to_string()
-                        .parse()
-                        .expect("could not parse number literal raw value"),
-                )
-            } else {
-                NumberNodeValue::Int(
-                    raw.to_string()
-                        .parse()
-                        .expect("could not parse number literal raw value"),
-                )
-            };
-            NumberNode {
-                raw: raw.to_string(),
-                value,
-                span: *token.span(),
-            }
-        })
-    }
-
-    fn parse_boolean_literal(&mut self) -> Result<BooleanNode, String> {
-        self.consume(TokenKind::BooleanLiteral).map(|token| {
-            let raw = token.raw();
-            BooleanNode {
-                raw: raw.to_string(),
-                value: raw
-                    .to_string()
-                    .parse()
-                    .expect("could not parse boolean literal raw value"),
-                span: *token.span(),
-            }
-        })
-    }
+// Conversions of literal tokens into nodes, shared by all parsers.
 
-    fn parse_null_literal(&mut self) -> Result<NullNode, String> {
-        self.consume(TokenKind::NullLiteral).map(|token| NullNode {
-            raw: token.raw().to_string(),
-            span: *token.span(),
-        })
+fn string_literal(token: &Token) -> StringNode {
+    let raw = token.raw();
+    StringNode {
+        raw: raw.to_string(),
+        value: string_value(raw).to_string(),
+        span: *token.span(),
     }
+}
 
-    fn peek<'a>(&'a self, offset: usize) -> &'a Token {
-        // fn peek(&self, offset: usize) -> &'source Token {
-        self.tokens
-            .get(self.index + offset)
-            // .get(offset)
-            .expect("probably index out of range when peeking next token")
+fn number_literal(token: &Token) -> Result<NumberNode, String> {
+    Ok(NumberNode {
+        raw: token.raw().to_string(),
+        value: number_value(token)?,
+        span: *token.span(),
+    })
+}
+
+fn boolean_literal(token: &Token) -> BooleanNode {
+    let raw = token.raw();
+    BooleanNode {
+        raw: raw.to_string(),
+        value: boolean_value(raw),
+        span: *token.span(),
     }
+}
 
-    fn current<'a>(&'a self) -> &'a Token {
-        self.peek(0)
+fn null_literal(token: &Token) -> NullNode {
+    NullNode {
+        raw: token.raw().to_string(),
+        span: *token.span(),
     }
+}
 
-    /// consume and expect a specific token kind, returning the token of said kind,
-    /// or an error message if the next token was not of the expected kind.
-    // fn consume<'a>(&'a mut self, kind: TokenKind) -> Result<&'a Token, String> {
-    fn consume(&mut self, kind: TokenKind) -> Result<&Token, String> {
-        // self.index += 1;
+fn string_value(raw: &str) -> &str {
+    // FIXME: Maybe extract this, and also think about if there are any edge-cases
+    &raw[1..raw.len() - 1]
+}
 
-        // let token = self.current();
-        // FIXME: This would be the above, but I'm having problems with ownership
-        let token = self.tokens.get(self.index).expect("hello");
+/// The value of a number literal. Integers out of the range of `i64` become
+/// (rounded) floats, like in most JSON parsers.
+fn number_value(token: &Token) -> Result<NumberNodeValue, String> {
+    let raw = token.raw();
+    let value = match raw.parse() {
+        Ok(value) if !raw.contains('.') => Some(NumberNodeValue::Int(value)),
+        _ => raw.parse().map(NumberNodeValue::Float).ok(),
+    };
 
-        if token.kind() != &kind {
-            // TODO: add more detail
-            return Err(format!(
-                "unexpected token: found `{:?}`, expected `{:?}`",
-                token.kind(),
-                &kind
-            )
-            .to_string());
-        }
+    value.ok_or_else(|| located(format!("number `{}` out of range", raw), token.span()))
+}
+
+fn boolean_value(raw: &str) -> bool {
+    raw == "true"
+}
+
+fn depth_error(max_depth: usize, span: &Span) -> String {
+    located(format!("nesting deeper than {} levels", max_depth), span)
+}
 
-        self.index += 1;
+/// Appends where an error occurred to its message.
+fn located(message: String, span: &Span) -> String {
+    format!(
+        "{} at line {} col {}",
+        message,
+        span.start().line(),
+        span.start().column() + 1
+    )
+}
+
+/// Parses a document without comments, e.g. `"[1, 2]".parse::<Node>()`.
+impl FromStr for Node {
+    type Err = String;
 
-        Ok(token)
+    fn from_str(source: &str) -> Result<Node, String> {
+        Parser::new_without_comments().parse_str(source)
     }
 }
 
+/// Parses a test fixture, which must be well-formed.
+#[cfg(test)]
+pub(crate) fn parse_source(source: &str) -> Node {
+    source.parse().expect("could not parse source")
+}
+
 #[cfg(test)]
 mod tests {
     use super::node::Position;
@@ -233,16 +310,194 @@ mod tests {
     fn it_parses_null() {
         let mut parser = Parser::new_without_comments();
 
-        let ast = parser
-            .parse(&"null".to_string())
-            .expect("could not parse null");
+        let ast = parser.parse_str("null").expect("could not parse null");
 
         assert_eq!(
             ast,
             Node::Null(NullNode {
-                span: Span::new(Position::start(), Position::new(5, 1, 5)),
+                span: Span::new(Position::start(), Position::new(4, 1, 4)),
                 raw: "null".to_string(),
             })
         );
     }
+
+    #[test]
+    fn it_parses_large_integers_as_floats() {
+        let node = parse_source("[9223372036854775807, 99999999999999999999]");
+
+        assert_eq!(node[0].as_i64().unwrap(), i64::MAX);
+        match &node[1] {
+            Node::Number(number) => {
+                assert_eq!(number.value, NumberNodeValue::Float(1e20));
+                assert_eq!(number.raw, "99999999999999999999");
+            }
+            node => panic!("expected a number, found {:?}", node),
+        }
+    }
+
+    #[test]
+    fn it_slices_the_source_for_containers() {
+        let source = "{\"a\": [1,  2.5], \"b\" : \"x\"}";
+        let node = Parser::new_without_comments().parse(source).unwrap();
+
+        assert_eq!(node.raw_in(source), Some(source));
+        assert_eq!(node.raw(), "{\"a\":[1,2.5],\"b\":\"x\"}");
+        let a = node.pointer("/a").unwrap();
+        assert_eq!(a.raw_in(source), Some("[1,  2.5]"));
+        assert_eq!(Node::array(vec![]).raw_in(source), None);
+    }
+
+    #[test]
+    fn it_parses_slices_and_readers() {
+        let mut parser = Parser::new_without_comments();
+        assert_eq!(
+            parser.parse_slice(b"[1, \"a\"]"),
+            Ok("[1, \"a\"]".parse().unwrap())
+        );
+        assert_eq!(
+            parser.parse_slice(b"[1,\n  \"a\xff\"]"),
+            Err("invalid UTF-8 at byte 8, line 2 col 5".to_string())
+        );
+
+        let node = Parser::new_with_comments()
+            .deny_duplicate_keys()
+            .parse_reader("{\"a\": [true] /* c */}".as_bytes())
+            .unwrap();
+        assert!(node.is_object());
+        assert!(Parser::new_without_comments()
+            .deny_duplicate_keys()
+            .parse_reader("{\"a\": 1, \"a\": 2}".as_bytes())
+            .is_err());
+    }
+
+    /// Reads one byte at a time, splitting every token and character.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            match (self.0.split_first(), buffer.first_mut()) {
+                (Some((byte, rest)), Some(first)) => {
+                    *first = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn it_parses_readers_incrementally() {
+        let sources: [&[u8]; 12] = [
+            b"{\"a\": [1, 12.5, null, true],\n \"\xc3\xa9\": {\"b\": 'c'} /* d */ }  ",
+            b"// e\n[false, \"\", 10]",
+            b"1",
+            b"  null\n",
+            b"",
+            b"[1, x]",
+            b"[1.",
+            b"{\"a\" 1}",
+            b"\"abc",
+            b"[1] 2",
+            b"[1,\n  \"a\xff\"]",
+            b"\"\xc3",
+        ];
+
+        for source in sources {
+            let mut parser = Parser::new_with_comments();
+            let expected = parser.parse_slice(source);
+            assert_eq!(parser.parse_reader(Trickle(source)), expected);
+            assert_eq!(parser.parse_reader(source), expected);
+        }
+
+        // malformed input ends reading, even if there would be more
+        let endless = b"[1 2".chain(std::io::repeat(b' '));
+        assert_eq!(
+            Parser::new_without_comments().parse_reader(endless),
+            Err("unexpected token: found `NumberLiteral`, expected `Comma`".to_string())
+        );
+    }
+
+    #[test]
+    fn it_parses_without_recursing() {
+        let deep = "[".repeat(200_000);
+        assert_eq!(
+            Parser::new_without_comments()
+                .max_depth(usize::MAX)
+                .parse_str(&deep),
+            Err("unexpected token: found `Eoi`, expected `ClosedSquareBracket`".to_string())
+        );
+    }
+
+    #[test]
+    fn it_limits_nesting() {
+        let deep = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert_eq!(
+            Parser::new_without_comments().parse(&deep),
+            Err("nesting deeper than 128 levels at line 1 col 129".to_string())
+        );
+
+        let mut parser = Parser::new_without_comments().max_depth(2);
+        assert!(parser.parse("[{\"a\": 1}, []]").is_ok());
+        assert_eq!(
+            parser.parse("[1, {\"a\": [2]}]"),
+            Err("nesting deeper than 2 levels at line 1 col 11".to_string())
+        );
+        assert_eq!(
+            parser.parse_tape("[1, {\"a\": [2]}]").unwrap_err(),
+            parser.parse("[1, {\"a\": [2]}]").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn it_rejects_malformed_input() {
+        let cases = [
+            (
+                "[1, }",
+                "unexpected token: found `ClosedCurlyBrace`, expected a value",
+            ),
+            (
+                "{\"a\": ]",
+                "unexpected token: found `ClosedSquareBracket`, expected a value",
+            ),
+            ("\u{0661}", "unrecognized token `\u{0661}` at line 1 col 1"),
+            (
+                "{\"a\": [1,\n  nothing to see here, really]}",
+                "unrecognized token `nothing to see h...` at line 2 col 3",
+            ),
+        ];
+
+        for (source, expected) in cases {
+            assert_eq!(
+                Parser::new_without_comments().parse(source),
+                Err(expected.to_string())
+            );
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn it_never_panics_on_arbitrary_bytes(bytes: Vec<u8>) {
+            let _ = Parser::new_with_comments().parse_slice(&bytes);
+            let _ = Parser::new_without_comments().parse(&String::from_utf8_lossy(&bytes));
+        }
+
+        #[test]
+        fn it_parses_like_the_other_parsers(source in r#"[\[\]{}:, "'0-9.a-z/*\n]{0,40}"#) {
+            // errors may differ, as the other parsers tokenize lazily
+            let mut parser = Parser::new_with_comments().max_depth(4);
+            let expected = parser.parse(&source).ok();
+            let borrowed = parser.parse_borrowed(&source).ok().map(|node| node.to_node());
+            let tape = parser.parse_tape(&source).ok().map(|tape| tape.root().to_node());
+            proptest::prop_assert_eq!(&borrowed, &expected);
+            proptest::prop_assert_eq!(&tape, &expected);
+        }
+    }
+
+    #[test]
+    fn it_parses_from_str() {
+        let node: Node = "[null]".parse().expect("could not parse array");
+        assert!(node.is_array());
+        assert!("[".parse::<Node>().is_err());
+    }
 }
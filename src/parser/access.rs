@@ -0,0 +1,501 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use std::sync::OnceLock;
+
+use super::node::{ArrayNode, Node, NumberNodeValue, ObjectNode, Span, StringNode};
+use super::visit::{Path, PathSegment};
+
+/// An error from one of the typed accessors, naming the requested path, the
+/// type that was found and (unless it was synthesized) where it was found.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AccessError {
+    pub message: String,
+    /// The path that was requested.
+    pub path: Path,
+    /// The type of the node that was found instead, if any.
+    pub found: Option<&'static str>,
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.depth() == 0 {
+            write!(f, "{} at the root", self.message)?;
+        } else {
+            write!(f, "{} at {}", self.message, self.path)?;
+        }
+
+        if let Some(found) = self.found {
+            write!(f, ", found {}", found)?;
+        }
+
+        if let Some(span) = self.span {
+            write!(
+                f,
+                ", line {} col {}",
+                span.start().line(),
+                span.start().column() + 1
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+/// A node together with the path it was reached by, so that accessors can be
+/// chained (`node.get("key6")?.index(2)?.as_str()?`) and still report the
+/// full path on errors.
+#[derive(Debug, Clone)]
+pub struct NodeRef<'a> {
+    pub node: &'a Node,
+    pub path: Path,
+}
+
+impl<'a> NodeRef<'a> {
+    pub fn new(node: &'a Node) -> NodeRef<'a> {
+        NodeRef {
+            node,
+            path: Path::root(),
+        }
+    }
+
+    fn error(&self, message: String, path: Path) -> AccessError {
+        let span = self.node.span();
+        AccessError {
+            message,
+            path,
+            found: Some(self.node.type_name()),
+            span: Some(*span).filter(|span| !span.is_synthetic()),
+        }
+    }
+
+    fn mismatch(&self, expected: &str) -> AccessError {
+        self.error(format!("expected {}", expected), self.path.clone())
+    }
+
+    /// Gets the value of the given object member.
+    pub fn get(&self, key: &str) -> Result<NodeRef<'a>, AccessError> {
+        let path = self.path.child(PathSegment::Key(key.to_string()));
+        match self.node {
+            Node::Object(object) => match object.get(key) {
+                Some(node) => Ok(NodeRef { node, path }),
+                None => Err(self.error(format!("missing key `{}`", key), path)),
+            },
+            _ => Err(self.error("expected object".to_string(), path)),
+        }
+    }
+
+    /// Gets the array item at the given index.
+    pub fn index(&self, index: usize) -> Result<NodeRef<'a>, AccessError> {
+        let path = self.path.child(PathSegment::Index(index));
+        match self.node {
+            Node::Array(array) => match array.value.get(index) {
+                Some(node) => Ok(NodeRef { node, path }),
+                None => Err(self.error(
+                    format!(
+                        "index {} out of bounds for array of length {}",
+                        index,
+                        array.value.len()
+                    ),
+                    path,
+                )),
+            },
+            _ => Err(self.error("expected array".to_string(), path)),
+        }
+    }
+
+    /// Follows a simple dotted path like `a.b[0].c` (use [`Node::pointer`] for
+    /// keys containing `.` or `[`).
+    pub fn get_path(&self, path: &str) -> Result<NodeRef<'a>, AccessError> {
+        let mut current = self.clone();
+        for segment in parse_path(path).map_err(|message| AccessError {
+            message,
+            path: self.path.clone(),
+            found: None,
+            span: None,
+        })? {
+            current = match segment {
+                PathSegment::Key(key) => current.get(&key)?,
+                PathSegment::Index(index) => current.index(index)?,
+            };
+        }
+
+        Ok(current)
+    }
+
+    pub fn as_null(&self) -> Result<(), AccessError> {
+        match self.node {
+            Node::Null(_) => Ok(()),
+            _ => Err(self.mismatch("null")),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, AccessError> {
+        match self.node {
+            Node::Boolean(node) => Ok(node.value),
+            _ => Err(self.mismatch("boolean")),
+        }
+    }
+
+    pub fn as_i64(&self) -> Result<i64, AccessError> {
+        match self.node {
+            Node::Number(node) => match node.value {
+                NumberNodeValue::Int(value) => Ok(value),
+                NumberNodeValue::Float(_) => Err(self.mismatch("integer")),
+            },
+            _ => Err(self.mismatch("integer")),
+        }
+    }
+
+    /// Integers are converted to floats, which may lose precision.
+    pub fn as_f64(&self) -> Result<f64, AccessError> {
+        match self.node {
+            Node::Number(node) => match node.value {
+                NumberNodeValue::Int(value) => Ok(value as f64),
+                NumberNodeValue::Float(value) => Ok(value),
+            },
+            _ => Err(self.mismatch("number")),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&'a str, AccessError> {
+        match self.node {
+            Node::String(node) => Ok(&node.value),
+            _ => Err(self.mismatch("string")),
+        }
+    }
+
+    pub fn as_array(&self) -> Result<&'a ArrayNode, AccessError> {
+        match self.node {
+            Node::Array(node) => Ok(node),
+            _ => Err(self.mismatch("array")),
+        }
+    }
+
+    pub fn as_object(&self) -> Result<&'a ObjectNode, AccessError> {
+        match self.node {
+            Node::Object(node) => Ok(node),
+            _ => Err(self.mismatch("object")),
+        }
+    }
+}
+
+/// Splits a dotted path like `a.b[0]` into its segments.
+pub(crate) fn parse_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let invalid = |reason: &str| format!("invalid path `{}`: {}", path, reason);
+
+    let mut segments = vec![];
+    let mut rest = path;
+    let mut expect_key = true;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(|| invalid("unclosed `[`"))?;
+            let index = after[..end]
+                .parse()
+                .map_err(|_| invalid("index must be a non-negative integer"))?;
+            segments.push(PathSegment::Index(index));
+            rest = &after[end + 1..];
+            expect_key = false;
+            continue;
+        }
+
+        if !expect_key {
+            rest = rest
+                .strip_prefix('.')
+                .ok_or_else(|| invalid("expected `.` or `[`"))?;
+        }
+
+        let end = rest.find(['.', '[']).unwrap_or(rest.len());
+        if end == 0 {
+            return Err(invalid("empty key"));
+        }
+        segments.push(PathSegment::Key(rest[..end].to_string()));
+        rest = &rest[end..];
+        expect_key = false;
+    }
+
+    Ok(segments)
+}
+
+impl Node {
+    /// The name of this node's type, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Node::Null(_) => "null",
+            Node::Boolean(_) => "boolean",
+            Node::Number(_) => "number",
+            Node::String(_) => "string",
+            Node::Array(_) => "array",
+            Node::Object(_) => "object",
+        }
+    }
+
+    /// Starts a chain of accessors at this node; see [`NodeRef`].
+    pub fn at(&self) -> NodeRef<'_> {
+        NodeRef::new(self)
+    }
+
+    pub fn get(&self, key: &str) -> Result<NodeRef<'_>, AccessError> {
+        self.at().get(key)
+    }
+
+    pub fn index(&self, index: usize) -> Result<NodeRef<'_>, AccessError> {
+        self.at().index(index)
+    }
+
+    pub fn get_path(&self, path: &str) -> Result<NodeRef<'_>, AccessError> {
+        self.at().get_path(path)
+    }
+
+    pub fn as_bool(&self) -> Result<bool, AccessError> {
+        self.at().as_bool()
+    }
+
+    pub fn as_i64(&self) -> Result<i64, AccessError> {
+        self.at().as_i64()
+    }
+
+    pub fn as_f64(&self) -> Result<f64, AccessError> {
+        self.at().as_f64()
+    }
+
+    pub fn as_str(&self) -> Result<&str, AccessError> {
+        self.at().as_str()
+    }
+
+    pub fn as_array(&self) -> Result<&ArrayNode, AccessError> {
+        self.at().as_array()
+    }
+
+    pub fn as_object(&self) -> Result<&ObjectNode, AccessError> {
+        self.at().as_object()
+    }
+}
+
+/// Returned by indexing when a key or index does not exist.
+static NULL: OnceLock<Node> = OnceLock::new();
+
+/// Indexes into an object. Missing keys (or indexing anything but an
+/// object) give a synthetic null node instead of panicking, so that lookups
+/// like `node["a"]["b"]` can be chained.
+impl Index<&str> for Node {
+    type Output = Node;
+
+    fn index(&self, key: &str) -> &Node {
+        match self {
+            Node::Object(object) => object.get(key),
+            _ => None,
+        }
+        .unwrap_or_else(|| NULL.get_or_init(Node::null))
+    }
+}
+
+/// Indexes into an array. Out of bounds indices (or indexing anything but an
+/// array) give a synthetic null node instead of panicking.
+impl Index<usize> for Node {
+    type Output = Node;
+
+    fn index(&self, index: usize) -> &Node {
+        match self {
+            Node::Array(array) => array.value.get(index),
+            _ => None,
+        }
+        .unwrap_or_else(|| NULL.get_or_init(Node::null))
+    }
+}
+
+/// Mutably indexes into an object, inserting a null member if the key does
+/// not exist. A null node is turned into an empty object first.
+///
+/// # Panics
+///
+/// Panics if the node is neither an object nor null.
+impl IndexMut<&str> for Node {
+    fn index_mut(&mut self, key: &str) -> &mut Node {
+        if self.is_null() {
+            *self = Node::object(vec![]);
+        }
+
+        match self {
+            Node::Object(object) => {
+                if !object.contains_key(key) {
+                    object.insert(StringNode::synthetic(key), Node::null());
+                }
+                object.get_mut(key).unwrap()
+            }
+            node => panic!("cannot index into {} with key `{}`", node.type_name(), key),
+        }
+    }
+}
+
+/// Mutably indexes into an array.
+///
+/// # Panics
+///
+/// Panics if the node is not an array or the index is out of bounds.
+impl IndexMut<usize> for Node {
+    fn index_mut(&mut self, index: usize) -> &mut Node {
+        match self {
+            Node::Array(array) => {
+                let len = array.value.len();
+                array.value.get_mut(index).unwrap_or_else(|| {
+                    panic!("index {} out of bounds for array of length {}", index, len)
+                })
+            }
+            node => panic!(
+                "cannot index into {} with index {}",
+                node.type_name(),
+                index
+            ),
+        }
+    }
+}
+
+impl TryFrom<&Node> for bool {
+    type Error = AccessError;
+
+    fn try_from(node: &Node) -> Result<bool, AccessError> {
+        node.as_bool()
+    }
+}
+
+impl TryFrom<&Node> for i64 {
+    type Error = AccessError;
+
+    fn try_from(node: &Node) -> Result<i64, AccessError> {
+        node.as_i64()
+    }
+}
+
+impl TryFrom<&Node> for u64 {
+    type Error = AccessError;
+
+    fn try_from(node: &Node) -> Result<u64, AccessError> {
+        let value = node.as_i64()?;
+        u64::try_from(value).map_err(|_| {
+            node.at().error(
+                format!("expected u64, `{}` is negative", value),
+                Path::root(),
+            )
+        })
+    }
+}
+
+impl TryFrom<&Node> for f64 {
+    type Error = AccessError;
+
+    fn try_from(node: &Node) -> Result<f64, AccessError> {
+        node.as_f64()
+    }
+}
+
+impl<'a> TryFrom<&'a Node> for &'a str {
+    type Error = AccessError;
+
+    fn try_from(node: &'a Node) -> Result<&'a str, AccessError> {
+        node.as_str()
+    }
+}
+
+impl TryFrom<&Node> for String {
+    type Error = AccessError;
+
+    fn try_from(node: &Node) -> Result<String, AccessError> {
+        node.as_str().map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    #[test]
+    fn it_accesses_values() -> Result<(), AccessError> {
+        let root =
+            parse_source("{\"key6\": [\"a\", 1, 2.5, true], \"a\": {\"b\": [{\"c\": null}]}}");
+
+        assert_eq!(root.get("key6")?.index(0)?.as_str()?, "a");
+        assert_eq!(root.get("key6")?.index(1)?.as_i64()?, 1);
+        assert_eq!(root.get("key6")?.index(1)?.as_f64()?, 1.0);
+        assert!(root.get("key6")?.index(3)?.as_bool()?);
+        assert_eq!(root.get("key6")?.as_array()?.value.len(), 4);
+        root.get_path("a.b[0].c")?.as_null()?;
+        assert_eq!(root.get_path("a.b[0]")?.path.pointer(), "/a/b/0");
+        assert_eq!(root.as_object()?.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_reports_paths_types_and_spans() {
+        let root = parse_source("{\"key6\": [\"a\", 1]}");
+
+        let error = root
+            .get("key6")
+            .unwrap()
+            .index(1)
+            .unwrap()
+            .as_str()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected string at /key6/1, found number, line 1 col 16"
+        );
+
+        let error = root.get_path("key6[5]").unwrap_err();
+        assert_eq!(error.path.pointer(), "/key6/5");
+        assert_eq!(error.found, Some("array"));
+
+        let error = root.get_path("missing.x").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "missing key `missing` at /missing, found object, line 1 col 1"
+        );
+
+        assert!(root.get_path("key6[x]").is_err());
+        assert!(root.get_path("key6..a").is_err());
+    }
+
+    #[test]
+    fn it_indexes() {
+        let root = parse_source("{\"key6\": [\"a\", 1]}");
+
+        assert_eq!(root["key6"][0].as_str().unwrap(), "a");
+        assert!(root["missing"][3]["x"].is_null());
+        assert!(root["key6"]["x"].is_null());
+        assert_eq!(i64::try_from(&root["key6"][1]).unwrap(), 1);
+        assert_eq!(<&str>::try_from(&root["key6"][0]).unwrap(), "a");
+        assert!(bool::try_from(&root["key6"][0]).is_err());
+
+        let mut root = root;
+        root["key6"][1] = Node::int(2);
+        root["new"]["nested"] = Node::boolean(true);
+        assert_eq!(root["key6"][1].as_i64().unwrap(), 2);
+        assert!(root["new"]["nested"].as_bool().unwrap());
+        assert!(root.pointer("/new/nested").is_ok());
+
+        // inserted members keep their order, after the parsed ones
+        root["z"] = Node::int(1);
+        root["a"] = Node::int(2);
+        root["z"] = Node::int(3);
+        let keys = |root: &Node| -> Vec<String> {
+            match root {
+                Node::Object(object) => object.entries().map(|(key, _)| key.clone()).collect(),
+                _ => vec![],
+            }
+        };
+        assert_eq!(keys(&root), vec!["key6", "new", "z", "a"]);
+
+        if let Node::Object(object) = &mut root {
+            assert!(object.remove("new").is_some());
+        }
+        assert_eq!(keys(&root), vec!["key6", "z", "a"]);
+        assert_eq!(root["a"].as_i64().unwrap(), 2);
+        assert_eq!(root["z"].as_i64().unwrap(), 3);
+    }
+}
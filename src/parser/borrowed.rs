@@ -0,0 +1,253 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use super::node::{
+    ArrayNode, BooleanNode, Node, NullNode, NumberNode, NumberNodeValue, ObjectNode, Span,
+    StringNode,
+};
+use super::pull::{PullParser, RawEvent};
+use super::tokenizer::{Token, TokenKind};
+use super::{boolean_value, number_value, string_value, Parser};
+
+/// A node borrowing its raw text from the source, see
+/// [`Parser::parse_borrowed`]. Unlike [`Node`], not even scalars copy their
+/// raw text from the source.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BorrowedNode<'source> {
+    pub span: Span,
+    pub raw: &'source str,
+    pub value: BorrowedValue<'source>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum BorrowedValue<'source> {
+    Null,
+    Boolean(bool),
+    Number(NumberNodeValue),
+    /// Borrowed from the source, unless the value needed unescaping.
+    String(Cow<'source, str>),
+    Array(Vec<BorrowedNode<'source>>),
+    /// Members in source order. A duplicate key keeps the position of its
+    /// first occurrence and the value of its last one.
+    Object(Vec<(BorrowedKey<'source>, BorrowedNode<'source>)>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BorrowedKey<'source> {
+    pub span: Span,
+    pub raw: &'source str,
+    /// Borrowed from the source, unless the key needed unescaping.
+    pub value: Cow<'source, str>,
+}
+
+impl<'source> BorrowedNode<'source> {
+    /// The value of the member with the given key, if this is an object.
+    pub fn get(&self, key: &str) -> Option<&BorrowedNode<'source>> {
+        match &self.value {
+            BorrowedValue::Object(members) => members
+                .iter()
+                .find(|(member, _)| member.value == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Copies this tree into an owned [`Node`], e.g. to drop the source.
+    pub fn to_node(&self) -> Node {
+        let span = self.span;
+        // containers do not keep their raw text
+        let raw = || self.raw.to_string();
+        match &self.value {
+            BorrowedValue::Null => Node::Null(NullNode { span, raw: raw() }),
+            BorrowedValue::Boolean(value) => Node::Boolean(BooleanNode {
+                span,
+                value: *value,
+                raw: raw(),
+            }),
+            BorrowedValue::Number(value) => Node::Number(NumberNode {
+                span,
+                value: value.clone(),
+                raw: raw(),
+            }),
+            BorrowedValue::String(value) => Node::String(StringNode {
+                span,
+                value: value.to_string(),
+                raw: raw(),
+            }),
+            BorrowedValue::Array(items) => Node::Array(ArrayNode {
+                span,
+                value: items.iter().map(BorrowedNode::to_node).collect(),
+            }),
+            BorrowedValue::Object(members) => {
+                let mut object = ObjectNode::empty(span);
+                for (key, member) in members {
+                    let key_node = StringNode {
+                        span: key.span,
+                        value: key.value.to_string(),
+                        raw: key.raw.to_string(),
+                    };
+                    object.insert(key_node, member.to_node());
+                }
+
+                Node::Object(object)
+            }
+        }
+    }
+}
+
+impl<'source> Parser<'source> {
+    /// Parses `source` like [`Parser::parse_str`], into a tree borrowing from
+    /// it rather than copying it.
+    pub fn parse_borrowed(
+        &mut self,
+        source: &'source str,
+    ) -> Result<BorrowedNode<'source>, String> {
+        let mut events = PullParser::new(source, self.tokenizer.comments())
+            .max_depth(self.max_depth)
+            .limits(self.limits);
+        let node = read(&mut events, source, self.deny_duplicate_keys)?;
+        // this checks that nothing follows the value
+        events.next_raw()?;
+
+        Ok(node)
+    }
+}
+
+fn read<'source>(
+    events: &mut PullParser<'source>,
+    source: &'source str,
+    deny_duplicate_keys: bool,
+) -> Result<BorrowedNode<'source>, String> {
+    let (span, value) = match next(events)? {
+        RawEvent::Scalar(token) => {
+            let raw = token.raw();
+            let value = match token.kind() {
+                TokenKind::NullLiteral => BorrowedValue::Null,
+                TokenKind::BooleanLiteral => BorrowedValue::Boolean(boolean_value(raw)),
+                TokenKind::NumberLiteral => BorrowedValue::Number(number_value(&token)?),
+                _ => BorrowedValue::String(Cow::Borrowed(string_value(raw))),
+            };
+            (*token.span(), value)
+        }
+        RawEvent::StartArray(_) => {
+            let mut items = vec![];
+            loop {
+                match next(events)? {
+                    RawEvent::EndArray(span) => break (span, BorrowedValue::Array(items)),
+                    event => {
+                        events.push_back(event);
+                        items.push(read(events, source, deny_duplicate_keys)?);
+                    }
+                }
+            }
+        }
+        RawEvent::StartObject(_) => {
+            let mut members: Vec<(BorrowedKey, BorrowedNode)> = vec![];
+            let mut positions = HashMap::new();
+            loop {
+                match next(events)? {
+                    RawEvent::EndObject(span) => break (span, BorrowedValue::Object(members)),
+                    RawEvent::Key(token) => {
+                        let key = key(&token);
+                        let value = read(events, source, deny_duplicate_keys)?;
+                        match positions.get(&key.value) {
+                            Some(_) if deny_duplicate_keys => {
+                                return Err(format!(
+                                    "duplicate key `{}` at line {} col {}",
+                                    key.value,
+                                    key.span.start().line(),
+                                    key.span.start().column() + 1
+                                ))
+                            }
+                            Some(&position) => members[position] = (key, value),
+                            None => {
+                                positions.insert(key.value.clone(), members.len());
+                                members.push((key, value));
+                            }
+                        }
+                    }
+                    _ => return Err("expected a key or the end of an object".to_string()),
+                }
+            }
+        }
+        _ => return Err("expected a value".to_string()),
+    };
+
+    Ok(BorrowedNode {
+        span,
+        raw: &source[span.start().cursor()..span.end().cursor()],
+        value,
+    })
+}
+
+fn next<'source>(events: &mut PullParser<'source>) -> Result<RawEvent<'source>, String> {
+    events
+        .next_raw()?
+        .ok_or_else(|| "unexpected end of input".to_string())
+}
+
+fn key<'source>(token: &Token<'source>) -> BorrowedKey<'source> {
+    BorrowedKey {
+        span: *token.span(),
+        raw: token.raw(),
+        value: Cow::Borrowed(string_value(token.raw())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "{\"a\": [1, 2.5, true, null], \"b\": {\"c\": \"d\"}}";
+
+    #[test]
+    fn it_borrows_from_the_source() {
+        let node = Parser::new_without_comments()
+            .parse_borrowed(SOURCE)
+            .unwrap();
+        assert_eq!(node.to_node(), SOURCE.parse::<Node>().unwrap());
+
+        let c = node.get("b").and_then(|b| b.get("c")).unwrap();
+        assert_eq!(c.raw, "\"d\"");
+        assert!(std::ptr::eq(c.raw, &SOURCE[39..42]));
+        assert!(matches!(
+            &c.value,
+            BorrowedValue::String(Cow::Borrowed("d"))
+        ));
+
+        let keys: Vec<_> = match &node.value {
+            BorrowedValue::Object(members) => members.iter().map(|(key, _)| key.raw).collect(),
+            _ => vec![],
+        };
+        assert_eq!(keys, vec!["\"a\"", "\"b\""]);
+    }
+
+    #[test]
+    fn it_parses_like_the_parser() {
+        let source = "{\"a\": 1, \"b\": 2, \"a\": [3]}";
+        let node = Parser::new_without_comments()
+            .parse_borrowed(source)
+            .unwrap();
+        assert_eq!(node.to_node(), source.parse::<Node>().unwrap());
+        match &node.value {
+            BorrowedValue::Object(members) => assert_eq!(members[0].1.raw, "[3]"),
+            value => panic!("expected an object, got {:?}", value),
+        }
+
+        assert_eq!(
+            Parser::new_without_comments()
+                .deny_duplicate_keys()
+                .parse_borrowed(source),
+            Err("duplicate key `a` at line 1 col 18".to_string())
+        );
+
+        for source in ["[1 2]", "{\"a\" 1}", "[1,", "1 2", "/* c */ 1", "{\"a\": }"] {
+            assert_eq!(
+                Parser::new_without_comments().parse_borrowed(source),
+                Err(source.parse::<Node>().unwrap_err()),
+                "parsing {:?}",
+                source
+            );
+        }
+    }
+}
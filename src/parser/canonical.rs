@@ -0,0 +1,360 @@
+use super::node::{quote_string, Node, NumberNodeValue};
+use super::visit::{Path, PathSegment};
+use super::Parser;
+
+/// The largest integer up to which every integer can be represented exactly
+/// as an IEEE 754 double, which is how RFC 8785 treats numbers.
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// Serializes `node` per the JSON Canonicalization Scheme (RFC 8785): object
+/// members sorted by the UTF-16 code units of their keys, numbers formatted
+/// like ECMAScript does, minimal string escaping and no whitespace.
+///
+/// Strings are written by their value, so escapes in the source are decoded
+/// first. This fails for strings that are not valid JSON string literals
+/// (e.g. single-quoted ones or ones with lone surrogates), for keys that are
+/// only equal once decoded, and for numbers that can not be represented as
+/// IEEE 754 doubles, i.e. non-finite floats and integers beyond 2^53 in
+/// magnitude. A tree can not contain duplicate keys otherwise; use
+/// [`canonicalize_source`] to reject them in the source.
+pub fn canonicalize(node: &Node) -> Result<String, String> {
+    let mut output = String::new();
+    write(node, &mut Path::root(), &mut output)?;
+    Ok(output)
+}
+
+/// Parses and canonicalizes `source`, failing for objects with duplicate keys.
+pub fn canonicalize_source(source: &str) -> Result<String, String> {
+    let node = Parser::new_without_comments()
+        .deny_duplicate_keys()
+        .parse_str(source)?;
+
+    canonicalize(&node)
+}
+
+impl Node {
+    /// See [`canonicalize`].
+    pub fn to_canonical_string(&self) -> Result<String, String> {
+        canonicalize(self)
+    }
+}
+
+fn write(node: &Node, path: &mut Path, output: &mut String) -> Result<(), String> {
+    match node {
+        Node::Null(_) => output.push_str("null"),
+        Node::Boolean(node) => output.push_str(if node.value { "true" } else { "false" }),
+        Node::Number(number) => {
+            let formatted = match number.value {
+                NumberNodeValue::Int(value) if value.unsigned_abs() <= MAX_SAFE_INTEGER as u64 => {
+                    value.to_string()
+                }
+                NumberNodeValue::Int(value) => {
+                    return Err(format!(
+                        "can not canonicalize {} at {}: integers beyond 2^53 can not be represented exactly",
+                        value,
+                        describe(path)
+                    ))
+                }
+                NumberNodeValue::Float(value) if value.is_finite() => format_number(value),
+                NumberNodeValue::Float(value) => {
+                    return Err(format!(
+                        "can not canonicalize {} at {}: only finite numbers are allowed",
+                        value,
+                        describe(path)
+                    ))
+                }
+            };
+            output.push_str(&formatted);
+        }
+        Node::String(node) => output.push_str(&quote_string(&string_value(&node.raw, path)?)),
+        Node::Array(array) => {
+            output.push('[');
+            for (index, item) in array.value.iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                path.push(PathSegment::Index(index));
+                write(item, path, output)?;
+                path.pop();
+            }
+            output.push(']');
+        }
+        Node::Object(object) => {
+            let mut members = vec![];
+            for (key, value) in object.members() {
+                path.push(PathSegment::Key(key.value.clone()));
+                members.push((string_value(&key.raw, path)?, &key.value, value));
+                path.pop();
+            }
+            members.sort_by(|(a, ..), (b, ..)| a.encode_utf16().cmp(b.encode_utf16()));
+
+            if let Some(pair) = members.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+                return Err(format!(
+                    "can not canonicalize {} at {}: duplicate key `{}`",
+                    node.raw(),
+                    describe(path),
+                    pair[1].0
+                ));
+            }
+
+            output.push('{');
+            for (index, (key, name, value)) in members.into_iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                output.push_str(&quote_string(&key));
+                output.push(':');
+                path.push(PathSegment::Key(name.clone()));
+                write(value, path, output)?;
+                path.pop();
+            }
+            output.push('}');
+        }
+    }
+
+    Ok(())
+}
+
+/// The value of the string literal `raw`. The parser keeps the escapes of
+/// strings in their value (and allows single quotes), so they are decoded
+/// here.
+fn string_value(raw: &str, path: &Path) -> Result<String, String> {
+    let error = |reason: &str| {
+        format!(
+            "can not canonicalize {} at {}: {}",
+            raw,
+            describe(path),
+            reason
+        )
+    };
+
+    let inner = raw
+        .strip_prefix('"')
+        .and_then(|raw| raw.strip_suffix('"'))
+        .ok_or_else(|| error("strings must be double-quoted"))?;
+
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('"') => '"',
+                Some('\\') => '\\',
+                Some('/') => '/',
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('u') => {
+                    let unit = code_unit(&mut chars).ok_or_else(|| error("invalid \\u escape"))?;
+                    let code = match unit {
+                        0xD800..=0xDBFF => {
+                            let low = match (chars.next(), chars.next()) {
+                                (Some('\\'), Some('u')) => code_unit(&mut chars),
+                                _ => None,
+                            };
+                            match low {
+                                Some(low @ 0xDC00..=0xDFFF) => {
+                                    0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)
+                                }
+                                _ => return Err(error("lone surrogates are not allowed")),
+                            }
+                        }
+                        0xDC00..=0xDFFF => return Err(error("lone surrogates are not allowed")),
+                        unit => unit,
+                    };
+                    char::from_u32(code).ok_or_else(|| error("invalid \\u escape"))?
+                }
+                _ => return Err(error("invalid escape")),
+            },
+            c if (c as u32) < 0x20 => return Err(error("control characters must be escaped")),
+            c => c,
+        };
+        value.push(c);
+    }
+
+    Ok(value)
+}
+
+/// The UTF-16 code unit of the four hex digits of a `\u` escape.
+fn code_unit(chars: &mut std::str::Chars) -> Option<u32> {
+    let mut unit = 0;
+    for _ in 0..4 {
+        unit = unit * 16 + chars.next()?.to_digit(16)?;
+    }
+    Some(unit)
+}
+
+fn describe(path: &Path) -> String {
+    match path.depth() {
+        0 => "the root".to_string(),
+        _ => path.to_string(),
+    }
+}
+
+/// Formats a finite double like ECMAScript's `Number.prototype.toString`.
+fn format_number(value: f64) -> String {
+    if value == 0.0 {
+        // this includes -0
+        return "0".to_string();
+    }
+
+    // the shortest digits that round-trip, e.g. `1.2345e-7`
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .unwrap_or((scientific.as_str(), "0"));
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+
+    let k = digits.len() as i32;
+    // the position of the decimal point relative to the digits
+    let n = exponent + 1;
+
+    let formatted = if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat(-n as usize), digits)
+    } else {
+        let sign = if n - 1 < 0 { '-' } else { '+' };
+        match k {
+            1 => format!("{}e{}{}", digits, sign, (n - 1).abs()),
+            _ => format!(
+                "{}.{}e{}{}",
+                &digits[..1],
+                &digits[1..],
+                sign,
+                (n - 1).abs()
+            ),
+        }
+    };
+
+    if value < 0.0 {
+        format!("-{}", formatted)
+    } else {
+        formatted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_canonicalizes_documents() {
+        let canonical = canonicalize_source(
+            "{\n  \"b\": [1, 2.50, \"x\"],\n  \"a\": {\"d\": true, \"c\": null},\n  \"\u{20ac}\": 1, \"\u{1f600}\": 2, \"\u{fb33}\": 3\n}",
+        )
+        .unwrap();
+
+        // U+1F600 is a surrogate pair in UTF-16 and thus sorts before U+FB33
+        assert_eq!(
+            canonical,
+            "{\"a\":{\"c\":null,\"d\":true},\"b\":[1,2.5,\"x\"],\"\u{20ac}\":1,\"\u{1f600}\":2,\"\u{fb33}\":3}"
+        );
+
+        let node = Node::array(vec![Node::string("tab\there \"quoted\" \u{1}")]);
+        assert_eq!(
+            node.to_canonical_string().unwrap(),
+            "[\"tab\\there \\\"quoted\\\" \\u0001\"]"
+        );
+    }
+
+    #[test]
+    fn it_decodes_escapes() {
+        let cases = [
+            ("\"\\u0041\\n\"", "\"A\\n\""),
+            ("\"\\/\\ud83d\\ude00\\u00e9\"", "\"/\u{1f600}\u{e9}\""),
+            (
+                "{\"\\u0062\": 1, \"a\\t\": 2, \"a\": 3}",
+                "{\"a\":3,\"a\\t\":2,\"b\":1}",
+            ),
+        ];
+
+        for (source, expected) in cases {
+            assert_eq!(
+                canonicalize_source(source).unwrap(),
+                expected,
+                "for {}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn it_formats_numbers_like_ecmascript() {
+        let cases = [
+            (0.0, "0"),
+            (-0.0, "0"),
+            (1.0, "1"),
+            (-1.5, "-1.5"),
+            (4.50, "4.5"),
+            (2e-3, "0.002"),
+            (0.000001, "0.000001"),
+            (1e-7, "1e-7"),
+            (1e20, "100000000000000000000"),
+            (1e21, "1e+21"),
+            (1e30, "1e+30"),
+            (1.2345e-20, "1.2345e-20"),
+            (333_333_333.333_333_3, "333333333.3333333"),
+            (9007199254740992.0, "9007199254740992"),
+            (5e-324, "5e-324"),
+            (1.7976931348623157e308, "1.7976931348623157e+308"),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(format_number(value), expected, "formatting {:?}", value);
+        }
+    }
+
+    #[test]
+    fn it_rejects_what_can_not_be_canonicalized() {
+        let error = canonicalize_source("{\"a\": 1,\n \"a\": 2}").unwrap_err();
+        assert_eq!(error, "duplicate key `a` at line 2 col 2");
+
+        let error = canonicalize(&Node::object(vec![(
+            "a".to_string(),
+            Node::array(vec![Node::float(f64::NAN)]),
+        )]))
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "can not canonicalize NaN at /a/0: only finite numbers are allowed"
+        );
+
+        let cases = [
+            (
+                "['a']",
+                "can not canonicalize 'a' at /0: strings must be double-quoted",
+            ),
+            (
+                "{\"a\": \"\\ud800\"}",
+                "can not canonicalize \"\\ud800\" at /a: lone surrogates are not allowed",
+            ),
+            (
+                "[\"\\x\"]",
+                "can not canonicalize \"\\x\" at /0: invalid escape",
+            ),
+            (
+                "{\"a\": 1, \"\\u0061\": 2}",
+                "can not canonicalize {\"a\":1,\"\\u0061\":2} at the root: duplicate key `a`",
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(canonicalize_source(source).unwrap_err(), expected);
+        }
+
+        assert!(canonicalize(&Node::int(1 << 53)).is_err());
+        assert_eq!(
+            canonicalize(&Node::int(i64::MIN)).unwrap_err(),
+            "can not canonicalize -9223372036854775808 at the root: integers beyond 2^53 can not be represented exactly"
+        );
+        assert_eq!(
+            canonicalize(&Node::int(-(1 << 53) + 1)).unwrap(),
+            "-9007199254740991"
+        );
+    }
+}
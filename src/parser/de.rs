@@ -0,0 +1,389 @@
+use std::fmt;
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use super::node::{Node, NumberNodeValue, ObjectNode, Span, StringNode};
+use super::visit::{Path, PathSegment};
+
+/// A deserialization error, annotated with the JSON path and (unless the
+/// offending node was synthesized) the source span of the offending node.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Error {
+    pub message: String,
+    pub path: Option<Path>,
+    pub span: Option<Span>,
+}
+
+impl Error {
+    /// Annotates this error with a location, unless it already has one from
+    /// a node further down the tree.
+    fn at(mut self, path: &Path, span: &Span) -> Error {
+        if self.path.is_none() {
+            self.path = Some(path.clone());
+            self.span = Some(*span).filter(|span| !span.is_synthetic());
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        match &self.path {
+            Some(path) if path.depth() == 0 => write!(f, " at the root")?,
+            Some(path) => write!(f, " at {}", path)?,
+            None => {}
+        }
+
+        if let Some(span) = self.span {
+            write!(
+                f,
+                ", line {} col {}",
+                span.start().line(),
+                span.start().column() + 1
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Error {
+        Error {
+            message: message.to_string(),
+            path: None,
+            span: None,
+        }
+    }
+}
+
+/// Deserializes a `T` from a parsed [`Node`], borrowing strings from it
+/// where possible.
+pub fn from_node<'a, T: de::Deserialize<'a>>(node: &'a Node) -> Result<T, Error> {
+    T::deserialize(Deserializer::new(node))
+}
+
+/// A [`serde::Deserializer`] reading from a [`Node`] tree.
+pub struct Deserializer<'a> {
+    node: &'a Node,
+    path: Path,
+}
+
+impl<'a> Deserializer<'a> {
+    pub fn new(node: &'a Node) -> Deserializer<'a> {
+        Deserializer {
+            node,
+            path: Path::root(),
+        }
+    }
+
+    fn child(&self, segment: PathSegment, node: &'a Node) -> Deserializer<'a> {
+        Deserializer {
+            node,
+            path: self.path.child(segment),
+        }
+    }
+
+    fn locate<T>(&self, result: Result<T, Error>) -> Result<T, Error> {
+        result.map_err(|error| error.at(&self.path, self.node.span()))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let result = match self.node {
+            Node::Null(_) => visitor.visit_unit(),
+            Node::Boolean(node) => visitor.visit_bool(node.value),
+            Node::Number(node) => match node.value {
+                NumberNodeValue::Int(value) => visitor.visit_i64(value),
+                NumberNodeValue::Float(value) => visitor.visit_f64(value),
+            },
+            Node::String(node) => visitor.visit_borrowed_str(&node.value),
+            Node::Array(array) => visitor.visit_seq(SeqAccess {
+                parent: &self,
+                items: array.value.iter().enumerate(),
+            }),
+            Node::Object(object) => visitor.visit_map(MapAccess {
+                parent: &self,
+                object,
+                entries: object.entries().collect::<Vec<_>>().into_iter(),
+                value: None,
+            }),
+        };
+
+        self.locate(result)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let result = match self.node {
+            Node::Null(_) => visitor.visit_none(),
+            _ => return visitor.visit_some(self),
+        };
+
+        self.locate(result)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let member = match self.node {
+            Node::Object(object) if object.len() == 1 => object.members().next(),
+            _ => None,
+        };
+        let result = match (self.node, member) {
+            // unit variants are represented as plain strings
+            (Node::String(node), _) => visitor.visit_enum(node.value.as_str().into_deserializer()),
+            // every other variant as an object with a single member
+            (_, Some((key, value))) => visitor.visit_enum(EnumAccess {
+                key: Some(key),
+                name: &key.value,
+                value: self.child(PathSegment::Key(key.value.clone()), value),
+            }),
+            _ => Err(de::Error::invalid_type(unexpected(self.node), &"an enum")),
+        };
+
+        self.locate(result)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+fn unexpected(node: &Node) -> de::Unexpected<'_> {
+    match node {
+        Node::Null(_) => de::Unexpected::Unit,
+        Node::Boolean(node) => de::Unexpected::Bool(node.value),
+        Node::Number(node) => match node.value {
+            NumberNodeValue::Int(value) => de::Unexpected::Signed(value),
+            NumberNodeValue::Float(value) => de::Unexpected::Float(value),
+        },
+        Node::String(node) => de::Unexpected::Str(&node.value),
+        Node::Array(_) => de::Unexpected::Seq,
+        Node::Object(_) => de::Unexpected::Map,
+    }
+}
+
+struct SeqAccess<'p, 'a> {
+    parent: &'p Deserializer<'a>,
+    items: std::iter::Enumerate<std::slice::Iter<'a, Node>>,
+}
+
+impl<'p, 'de> de::SeqAccess<'de> for SeqAccess<'p, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.items.next() {
+            Some((index, node)) => seed
+                .deserialize(self.parent.child(PathSegment::Index(index), node))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapAccess<'p, 'a> {
+    parent: &'p Deserializer<'a>,
+    object: &'a ObjectNode,
+    entries: std::vec::IntoIter<(&'a String, &'a Node)>,
+    value: Option<(&'a String, &'a Node)>,
+}
+
+impl<'p, 'de> de::MapAccess<'de> for MapAccess<'p, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let (key, node) = match self.entries.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        self.value = Some((key, node));
+
+        let key_deserializer: de::value::BorrowedStrDeserializer<Error> =
+            de::value::BorrowedStrDeserializer::new(key);
+        seed.deserialize(key_deserializer)
+            .map(Some)
+            .map_err(|error| match self.object.key(key) {
+                // errors about keys (e.g. unknown fields) point at the key itself
+                Some(key_node) => error.at(
+                    &self.parent.path.child(PathSegment::Key(key.clone())),
+                    &key_node.span,
+                ),
+                None => error,
+            })
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, node) = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing a key"))?;
+
+        seed.deserialize(self.parent.child(PathSegment::Key(key.clone()), node))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumAccess<'a> {
+    key: Option<&'a StringNode>,
+    name: &'a String,
+    value: Deserializer<'a>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = Deserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Error> {
+        let name: de::value::BorrowedStrDeserializer<Error> =
+            de::value::BorrowedStrDeserializer::new(self.name);
+        let variant = seed.deserialize(name).map_err(|error| match self.key {
+            Some(key) => error.at(&self.value.path, &key.span),
+            None => error,
+        })?;
+
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::parser::parse_source;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    struct Config<'a> {
+        name: &'a str,
+        server: Server,
+        tags: Vec<String>,
+        mode: Mode,
+        limits: Option<HashMap<String, f64>>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Server {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Mode {
+        Fast,
+        Custom { level: u8 },
+    }
+
+    #[test]
+    fn it_deserializes_structs() {
+        let root = parse_source(
+            "{\"name\": \"app\", \"server\": {\"host\": \"localhost\", \"port\": 8080}, \"tags\": [\"a\"], \"mode\": {\"Custom\": {\"level\": 3}}, \"limits\": null}",
+        );
+
+        let config: Config = from_node(&root).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                name: "app",
+                server: Server {
+                    host: "localhost".to_string(),
+                    port: 8080,
+                },
+                tags: vec!["a".to_string()],
+                mode: Mode::Custom { level: 3 },
+                limits: None,
+            }
+        );
+    }
+
+    #[test]
+    fn it_reports_errors_with_locations() {
+        let root = parse_source(
+            "{\n  \"name\": \"app\",\n  \"server\": {\n    \"port\": 70000,\n    \"host\": \"h\"\n  }\n}",
+        );
+        let error = from_node::<Config>(&root).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value: integer `70000`, expected u16 at /server/port, line 4 col 13"
+        );
+
+        let root = parse_source("{\"name\": \"app\", \"extra\": 1}");
+        let error = from_node::<Config>(&root).unwrap_err();
+        assert_eq!(error.path.unwrap().pointer(), "/extra");
+        assert_eq!(error.span.unwrap().start().cursor(), 16);
+
+        let root = parse_source("{\"host\": \"h\"}");
+        let error = from_node::<Server>(&root).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "missing field `port` at the root, line 1 col 1"
+        );
+
+        let root = parse_source("{\"mode\": \"Slow\"}");
+        let error = from_node::<HashMap<String, Mode>>(&root).unwrap_err();
+        assert_eq!(error.path.unwrap().pointer(), "/mode");
+    }
+}
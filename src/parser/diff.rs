@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::eq::NumberComparison;
+use super::node::{detached, ArrayNode, Node, ObjectNode, Span};
+use super::visit::{Path, PathSegment};
+
+/// How [`diff`] compares two trees.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DiffOptions {
+    /// Matches the items of arrays made up of objects by the value of this
+    /// member (e.g. `"id"`) instead of by their index. Arrays where not every
+    /// item is an object with a unique string or number under this key are
+    /// still compared by index.
+    pub array_key: Option<String>,
+    /// Numbers that differ by at most this much are considered equal.
+    pub float_tolerance: f64,
+}
+
+impl DiffOptions {
+    pub fn with_array_key(mut self, key: &str) -> DiffOptions {
+        self.array_key = Some(key.to_string());
+        self
+    }
+
+    pub fn with_float_tolerance(mut self, tolerance: f64) -> DiffOptions {
+        self.float_tolerance = tolerance;
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl ChangeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+        }
+    }
+}
+
+/// A single difference between two trees.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Change<'a> {
+    pub kind: ChangeKind,
+    /// The path in the new tree, or in the old one for removed values. These
+    /// only differ when array items are matched by key.
+    pub path: Path,
+    /// The value in the old tree, unless it was added.
+    pub old: Option<&'a Node>,
+    /// The value in the new tree, unless it was removed.
+    pub new: Option<&'a Node>,
+}
+
+impl<'a> Change<'a> {
+    pub fn old_span(&self) -> Option<Span> {
+        self.old.and_then(located)
+    }
+
+    pub fn new_span(&self) -> Option<Span> {
+        self.new.and_then(located)
+    }
+
+    /// The (synthesized) JSON representation of this change, e.g.
+    /// `{"kind": "changed", "path": "/a", "old": 1, "new": 2, "old_line": 1, ...}`.
+    pub fn to_node(&self) -> Node {
+        let mut members = vec![
+            ("kind".to_string(), Node::string(self.kind.name())),
+            ("path".to_string(), Node::string(&self.path.pointer())),
+        ];
+
+        for (side, node) in [("old", self.old), ("new", self.new)] {
+            let node = match node {
+                Some(node) => node,
+                None => continue,
+            };
+            members.push((side.to_string(), detached(node)));
+
+            if let Some(span) = located(node) {
+                let line = span.start().line() as i64;
+                let column = span.start().column() as i64 + 1;
+                members.push((format!("{}_line", side), Node::int(line)));
+                members.push((format!("{}_column", side), Node::int(column)));
+            }
+        }
+
+        Node::object(members)
+    }
+}
+
+impl fmt::Display for Change<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = match self.path.depth() {
+            0 => "/".to_string(),
+            _ => self.path.pointer(),
+        };
+        let location = |span: Option<Span>| match span {
+            Some(span) => format!(
+                "line {} col {}",
+                span.start().line(),
+                span.start().column() + 1
+            ),
+            None => "synthesized".to_string(),
+        };
+
+        match (self.old, self.new) {
+            (Some(old), Some(new)) => write!(
+                f,
+                "~ {}: {} -> {} ({} -> {})",
+                path,
+                old.raw(),
+                new.raw(),
+                location(self.old_span()),
+                location(self.new_span())
+            ),
+            (None, Some(new)) => write!(
+                f,
+                "+ {}: {} ({})",
+                path,
+                new.raw(),
+                location(self.new_span())
+            ),
+            (Some(old), _) => write!(
+                f,
+                "- {}: {} ({})",
+                path,
+                old.raw(),
+                location(self.old_span())
+            ),
+            (None, None) => write!(f, "? {}", path),
+        }
+    }
+}
+
+/// The differences between two trees, in document order.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Diff<'a> {
+    pub changes: Vec<Change<'a>>,
+}
+
+impl<'a> Diff<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The (synthesized) JSON array of all changes, see [`Change::to_node`].
+    pub fn to_node(&self) -> Node {
+        Node::array(self.changes.iter().map(Change::to_node).collect())
+    }
+}
+
+/// A human-readable report with one change per line.
+impl fmt::Display for Diff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares two trees by value, ignoring formatting and member order.
+pub fn diff<'a>(old: &'a Node, new: &'a Node, options: &DiffOptions) -> Diff<'a> {
+    let mut differ = Differ {
+        options,
+        changes: vec![],
+    };
+    differ.diff(old, new, &mut Path::root());
+    Diff {
+        changes: differ.changes,
+    }
+}
+
+fn located(node: &Node) -> Option<Span> {
+    Some(*node.span()).filter(|span| !span.is_synthetic())
+}
+
+struct Differ<'o, 'a> {
+    options: &'o DiffOptions,
+    changes: Vec<Change<'a>>,
+}
+
+impl<'o, 'a> Differ<'o, 'a> {
+    fn diff(&mut self, old: &'a Node, new: &'a Node, path: &mut Path) {
+        match (old, new) {
+            (Node::Object(old), Node::Object(new)) => self.diff_objects(old, new, path),
+            (Node::Array(old_array), Node::Array(new_array)) => {
+                match (self.keyed(old_array), self.keyed(new_array)) {
+                    (Some(old_keys), Some(new_keys)) => {
+                        self.diff_keyed(old_array, new_array, old_keys, new_keys, path)
+                    }
+                    _ => self.diff_arrays(old_array, new_array, path),
+                }
+            }
+            _ => {
+                let numbers = NumberComparison::Tolerance(self.options.float_tolerance);
+                if !old.semantic_eq_with(new, numbers) {
+                    self.change(ChangeKind::Changed, path, Some(old), Some(new));
+                }
+            }
+        }
+    }
+
+    fn diff_objects(&mut self, old: &'a ObjectNode, new: &'a ObjectNode, path: &mut Path) {
+        for (key, node) in old.entries() {
+            if !new.contains_key(key) {
+                path.push(PathSegment::Key(key.clone()));
+                self.change(ChangeKind::Removed, path, Some(node), None);
+                path.pop();
+            }
+        }
+
+        for (key, node) in new.entries() {
+            path.push(PathSegment::Key(key.clone()));
+            match old.get(key) {
+                Some(old) => self.diff(old, node, path),
+                None => self.change(ChangeKind::Added, path, None, Some(node)),
+            }
+            path.pop();
+        }
+    }
+
+    fn diff_arrays(&mut self, old: &'a ArrayNode, new: &'a ArrayNode, path: &mut Path) {
+        for (index, node) in new.value.iter().enumerate() {
+            path.push(PathSegment::Index(index));
+            match old.value.get(index) {
+                Some(old) => self.diff(old, node, path),
+                None => self.change(ChangeKind::Added, path, None, Some(node)),
+            }
+            path.pop();
+        }
+
+        for (index, node) in old.value.iter().enumerate().skip(new.value.len()) {
+            path.push(PathSegment::Index(index));
+            self.change(ChangeKind::Removed, path, Some(node), None);
+            path.pop();
+        }
+    }
+
+    fn diff_keyed(
+        &mut self,
+        old: &'a ArrayNode,
+        new: &'a ArrayNode,
+        old_keys: Vec<String>,
+        new_keys: Vec<String>,
+        path: &mut Path,
+    ) {
+        let old_indices: HashMap<_, _> = old_keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| (key, index))
+            .collect();
+        let new_indices: HashMap<_, _> = new_keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| (key, index))
+            .collect();
+
+        for (index, key) in old_keys.iter().enumerate() {
+            if !new_indices.contains_key(key) {
+                path.push(PathSegment::Index(index));
+                self.change(ChangeKind::Removed, path, Some(&old.value[index]), None);
+                path.pop();
+            }
+        }
+
+        for (index, key) in new_keys.iter().enumerate() {
+            let node = &new.value[index];
+            path.push(PathSegment::Index(index));
+            match old_indices.get(key) {
+                Some(old_index) => self.diff(&old.value[*old_index], node, path),
+                None => self.change(ChangeKind::Added, path, None, Some(node)),
+            }
+            path.pop();
+        }
+    }
+
+    /// The values of the array key of every item, if they are all unique.
+    fn keyed(&self, array: &ArrayNode) -> Option<Vec<String>> {
+        let key = self.options.array_key.as_ref()?;
+
+        let mut keys = Vec::with_capacity(array.value.len());
+        for item in &array.value {
+            let value = match item {
+                Node::Object(object) => object.get(key)?,
+                _ => return None,
+            };
+            let value = match value {
+                Node::String(string) => string.value.clone(),
+                Node::Number(number) => number.raw.clone(),
+                _ => return None,
+            };
+            if keys.contains(&value) {
+                return None;
+            }
+            keys.push(value);
+        }
+
+        Some(keys)
+    }
+
+    fn change(
+        &mut self,
+        kind: ChangeKind,
+        path: &Path,
+        old: Option<&'a Node>,
+        new: Option<&'a Node>,
+    ) {
+        self.changes.push(Change {
+            kind,
+            path: path.clone(),
+            old,
+            new,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    #[test]
+    fn it_reports_changes() {
+        let old = parse_source("{\"a\": 1, \"b\": [1, 2, 3], \"c\": {\"d\": true}}");
+        let new = parse_source(
+            "{\n  \"c\": {\"d\": false},\n  \"a\": 1.0,\n  \"b\": [1, 2],\n  \"e\": null\n}",
+        );
+
+        let diff = diff(&old, &new, &DiffOptions::default());
+        assert_eq!(
+            diff.to_string(),
+            "~ /c/d: true -> false (line 1 col 37 -> line 2 col 14)\n\
+             - /b/2: 3 (line 1 col 22)\n\
+             + /e: null (line 5 col 8)\n"
+        );
+
+        let kinds: Vec<_> = diff.changes.iter().map(|change| change.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![ChangeKind::Changed, ChangeKind::Removed, ChangeKind::Added]
+        );
+        assert_eq!(
+            diff.to_node().raw(),
+            "[{\"kind\":\"changed\",\"path\":\"/c/d\",\"old\":true,\"old_line\":1,\"old_column\":37,\"new\":false,\"new_line\":2,\"new_column\":14},\
+             {\"kind\":\"removed\",\"path\":\"/b/2\",\"old\":3,\"old_line\":1,\"old_column\":22},\
+             {\"kind\":\"added\",\"path\":\"/e\",\"new\":null,\"new_line\":5,\"new_column\":8}]"
+        );
+
+        assert!(super::diff(&old, &old, &DiffOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn it_matches_array_items_by_key() {
+        let old =
+            parse_source("[{\"id\": 1, \"v\": \"a\"}, {\"id\": 2, \"v\": \"b\"}, {\"id\": 3}]");
+        let new = parse_source("[{\"id\": 3}, {\"id\": 1, \"v\": \"c\"}, {\"id\": 4}]");
+
+        let options = DiffOptions::default().with_array_key("id");
+        let changes: Vec<_> = diff(&old, &new, &options)
+            .changes
+            .into_iter()
+            .map(|change| (change.kind, change.path.pointer()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (ChangeKind::Removed, "/1".to_string()),
+                (ChangeKind::Changed, "/1/v".to_string()),
+                (ChangeKind::Added, "/2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_uses_float_tolerance() {
+        let old = parse_source("[0.1, 1, 2.0]");
+        let new = parse_source("[0.1000001, 1, 2.5]");
+
+        let options = DiffOptions::default().with_float_tolerance(1e-3);
+        let diff = diff(&old, &new, &options);
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].path.pointer(), "/2");
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use super::node::{Node, NumberNodeValue};
+
+/// How numbers are compared by [`Node::semantic_eq_with`].
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum NumberComparison {
+    /// Integers and floats are never equal, so `1` and `1.0` differ.
+    Exact,
+    /// Numbers are compared by their mathematical value, so `1` and `1.0`
+    /// are equal. Integers are never rounded to floats for this.
+    #[default]
+    Value,
+    /// Numbers that differ by at most this much are equal. Two integers are
+    /// still compared exactly.
+    Tolerance(f64),
+}
+
+impl NumberComparison {
+    pub fn equal(&self, a: &NumberNodeValue, b: &NumberNodeValue) -> bool {
+        use NumberNodeValue::{Float, Int};
+
+        match (self, a, b) {
+            (_, Int(a), Int(b)) => a == b,
+            (_, Float(a), Float(b)) if a == b => true,
+            (NumberComparison::Exact, _, _) => false,
+            (NumberComparison::Value, Float(_), Float(_)) => false,
+            (NumberComparison::Value, Int(int), Float(float))
+            | (NumberComparison::Value, Float(float), Int(int)) => as_int(*float) == Some(*int),
+            (NumberComparison::Tolerance(tolerance), a, b) => {
+                (a.as_f64() - b.as_f64()).abs() <= *tolerance
+            }
+        }
+    }
+}
+
+/// The integer a float is equal to, if any.
+fn as_int(value: f64) -> Option<i64> {
+    // `i64::MIN` is a power of two and thus exactly representable, unlike `i64::MAX`
+    let range = i64::MIN as f64..-(i64::MIN as f64);
+    if value.fract() == 0.0 && range.contains(&value) {
+        Some(value as i64)
+    } else {
+        None
+    }
+}
+
+impl Node {
+    /// Compares the values of two trees, ignoring spans, raw source text and
+    /// the order of object members. Numbers are compared by value.
+    pub fn semantic_eq(&self, other: &Node) -> bool {
+        self.semantic_eq_with(other, NumberComparison::Value)
+    }
+
+    pub fn semantic_eq_with(&self, other: &Node, numbers: NumberComparison) -> bool {
+        match (self, other) {
+            (Node::Null(_), Node::Null(_)) => true,
+            (Node::Boolean(a), Node::Boolean(b)) => a.value == b.value,
+            (Node::Number(a), Node::Number(b)) => numbers.equal(&a.value, &b.value),
+            (Node::String(a), Node::String(b)) => a.value == b.value,
+            (Node::Array(a), Node::Array(b)) => {
+                a.value.len() == b.value.len()
+                    && a.value
+                        .iter()
+                        .zip(&b.value)
+                        .all(|(a, b)| a.semantic_eq_with(b, numbers))
+            }
+            (Node::Object(a), Node::Object(b)) => {
+                a.len() == b.len()
+                    && a.entries()
+                        .all(|(key, a)| b.get(key).is_some_and(|b| a.semantic_eq_with(b, numbers)))
+            }
+            _ => false,
+        }
+    }
+
+    /// Hashes the value of this tree, consistently with [`Node::semantic_eq`].
+    pub fn semantic_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Node::Null(_) => 0u8.hash(state),
+            Node::Boolean(node) => {
+                1u8.hash(state);
+                node.value.hash(state);
+            }
+            Node::Number(node) => {
+                let int = match node.value {
+                    NumberNodeValue::Int(value) => Some(value),
+                    NumberNodeValue::Float(value) => as_int(value),
+                };
+                match (int, &node.value) {
+                    (Some(value), _) => {
+                        2u8.hash(state);
+                        value.hash(state);
+                    }
+                    (None, value) => {
+                        3u8.hash(state);
+                        value.as_f64().to_bits().hash(state);
+                    }
+                }
+            }
+            Node::String(node) => {
+                4u8.hash(state);
+                node.value.hash(state);
+            }
+            Node::Array(array) => {
+                5u8.hash(state);
+                array.value.len().hash(state);
+                for item in &array.value {
+                    item.semantic_hash(state);
+                }
+            }
+            Node::Object(object) => {
+                6u8.hash(state);
+                object.len().hash(state);
+                // members are hashed separately and combined in a way that
+                // does not depend on their order
+                let members = object.entries().fold(0u64, |sum, (key, value)| {
+                    let mut hasher = DefaultHasher::new();
+                    key.hash(&mut hasher);
+                    value.semantic_hash(&mut hasher);
+                    sum.wrapping_add(hasher.finish())
+                });
+                members.hash(state);
+            }
+        }
+    }
+}
+
+/// Wraps a node so that `==` and `Hash` use [`Node::semantic_eq`] and
+/// [`Node::semantic_hash`], e.g. to put nodes into a `HashSet`.
+#[derive(Debug, Clone, Copy)]
+pub struct Semantic<'a>(pub &'a Node);
+
+impl PartialEq for Semantic<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.semantic_eq(other.0)
+    }
+}
+
+impl Eq for Semantic<'_> {}
+
+impl Hash for Semantic<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.semantic_hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::parser::parse_source;
+
+    #[test]
+    fn it_ignores_formatting() {
+        let a = parse_source("{\"a\": [1, 2.5, \"x\"], \"b\": {\"c\": null}}");
+        let b = parse_source("{\n  \"b\": {\"c\": null},\n  \"a\": [1.0, 2.5, \"x\"]\n}");
+
+        assert_ne!(a, b);
+        assert!(a.semantic_eq(&b));
+        assert!(!a.semantic_eq_with(&b, NumberComparison::Exact));
+        assert!(!a.semantic_eq(&parse_source("{\"a\": [1, 2.5, \"x\"]}")));
+    }
+
+    #[test]
+    fn it_compares_numbers() {
+        let (one, one_float) = (Node::int(1), Node::float(1.0));
+        assert!(one.semantic_eq(&one_float));
+        assert!(!one.semantic_eq_with(&one_float, NumberComparison::Exact));
+        assert!(Node::float(0.1 + 0.2)
+            .semantic_eq_with(&Node::float(0.3), NumberComparison::Tolerance(1e-9)));
+        assert!(!Node::float(0.1 + 0.2).semantic_eq(&Node::float(0.3)));
+
+        // large integers are not rounded to floats
+        assert!(!Node::int(i64::MAX).semantic_eq(&Node::float(i64::MAX as f64)));
+        assert!(Node::int(-0).semantic_eq(&Node::float(-0.0)));
+    }
+
+    #[test]
+    fn it_hashes_consistently() {
+        let a = parse_source("{\"x\": 1, \"y\": [true, 0.0]}");
+        let b = parse_source("{\"y\": [true, 0], \"x\": 1.0}");
+        let c = parse_source("{\"x\": 1, \"y\": [true, 0.5]}");
+
+        let set: HashSet<_> = [&a, &b, &c].into_iter().map(Semantic).collect();
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&Semantic(&b)));
+    }
+}
@@ -0,0 +1,310 @@
+use std::ops::Range;
+
+use super::node::{Node, Position, Span};
+use super::Parser;
+
+/// A change to a source text: the bytes in `range` of the old text are
+/// replaced with `replacement`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, replacement: &str) -> TextEdit {
+        TextEdit {
+            range,
+            replacement: replacement.to_string(),
+        }
+    }
+
+    /// Applies the edit to `source`, the text it was made against.
+    pub fn apply(&self, source: &str) -> Result<String, String> {
+        let parts = source
+            .get(..self.range.start)
+            .zip(source.get(self.range.end..))
+            .filter(|_| self.range.start <= self.range.end);
+
+        match parts {
+            Some((before, after)) => Ok(format!("{}{}{}", before, self.replacement, after)),
+            None => Err(format!(
+                "invalid edit range {}..{} for a source of {} bytes",
+                self.range.start,
+                self.range.end,
+                source.len()
+            )),
+        }
+    }
+
+    /// Where an old cursor at or after the end of the edit ends up.
+    fn shifted(&self, cursor: usize) -> usize {
+        cursor - self.range.end + self.range.start + self.replacement.len()
+    }
+}
+
+/// Moves positions at or after `from` so that `from` ends up at `to`.
+#[derive(Debug, Clone, Copy)]
+struct Shift {
+    from: Position,
+    to: Position,
+}
+
+impl Shift {
+    fn position(&self, position: Position) -> Position {
+        if position.cursor() < self.from.cursor() {
+            return position;
+        }
+
+        // columns only change on the line the shifted text starts on
+        let column = if position.line() == self.from.line() {
+            position.column() - self.from.column() + self.to.column()
+        } else {
+            position.column()
+        };
+
+        Position::new(
+            position.cursor() - self.from.cursor() + self.to.cursor(),
+            position.line() - self.from.line() + self.to.line(),
+            column,
+        )
+    }
+}
+
+impl<'source> Parser<'source> {
+    /// Parses `source`, the result of applying `edit` to the text `previous`
+    /// was parsed from with the same settings.
+    ///
+    /// Only the innermost value around the edit that still parses on its own
+    /// is parsed again; the rest of `previous` is updated in place, with the
+    /// spans after the edit shifted. The result is the same as that of
+    /// [`Parser::parse`], which is used when the edit can not be confined to
+    /// a single value.
+    ///
+    /// Tokens are not reused: the value around the edit is tokenized again
+    /// as a whole, and nothing outside of it is tokenized at all.
+    pub fn reparse(
+        &mut self,
+        mut previous: Node,
+        source: &'source str,
+        edit: &TextEdit,
+    ) -> Result<Node, String> {
+        if edit.range.start > edit.range.end {
+            return Err(format!(
+                "invalid edit range {}..{}",
+                edit.range.start, edit.range.end
+            ));
+        }
+
+        // limits on the whole document can only be checked by parsing all of it
+        if self.limits.max_tokens.is_some() || self.limits.max_input_size.is_some() {
+            return self.parse_str(source);
+        }
+
+        match self.reparse_value(&mut previous, 0, source, edit) {
+            Some(_) => Ok(previous),
+            None => self.parse_str(source),
+        }
+    }
+
+    /// Updates `node`, nested in `depth` containers, for the edit if the edit
+    /// is confined to it, returning how the positions after it moved. Leaves
+    /// `node` untouched otherwise.
+    fn reparse_value(
+        &self,
+        node: &mut Node,
+        depth: usize,
+        source: &str,
+        edit: &TextEdit,
+    ) -> Option<Shift> {
+        let span = *node.span();
+        let (start, end) = (span.start().cursor(), span.end().cursor());
+        let inside = match node {
+            // the brackets themselves must stay untouched
+            Node::Array(_) | Node::Object(_) => start < edit.range.start && edit.range.end < end,
+            _ => start <= edit.range.start && edit.range.end <= end,
+        };
+        if !inside || span.is_synthetic() {
+            return None;
+        }
+
+        let shift = match node {
+            Node::Array(array) => {
+                let mut shift = None;
+                for item in array.value.iter_mut() {
+                    match shift {
+                        Some(shift) => shift_spans(item, shift),
+                        None => shift = self.reparse_value(item, depth + 1, source, edit),
+                    }
+                }
+                shift
+            }
+            Node::Object(object) => {
+                let changed = object.members_mut().find_map(|(key, value)| {
+                    Some((
+                        key.value.clone(),
+                        self.reparse_value(value, depth + 1, source, edit)?,
+                    ))
+                });
+
+                changed.map(|(changed, shift)| {
+                    for (key, value) in object.members_mut() {
+                        if key.value != changed {
+                            shift_spans(value, shift);
+                        }
+                        key.span = shift_span(key.span, shift);
+                    }
+                    shift
+                })
+            }
+            _ => None,
+        };
+
+        if let Some(shift) = shift {
+            let new_span = shift_span(span, shift);
+            match node {
+                Node::Array(array) => array.span = new_span,
+                Node::Object(object) => object.span = new_span,
+                _ => {}
+            }
+
+            return Some(shift);
+        }
+
+        // the edit is not confined to a child, so parse this value again
+        let text = source.get(start..edit.shifted(end))?;
+        let mut parser = self.with_settings();
+        // the value is parsed on its own, but is still nested in the document
+        parser.max_depth = self.max_depth.saturating_sub(depth);
+        let mut reparsed = parser.parse_str(text).ok()?;
+        // e.g. trailing comments would swallow what follows in the document
+        let reparsed_span = reparsed.span();
+        if reparsed_span.start().cursor() != 0 || reparsed_span.end().cursor() != text.len() {
+            return None;
+        }
+
+        let base = span.start();
+        map_spans(&mut reparsed, &|position| {
+            Position::new(
+                base.cursor() + position.cursor(),
+                base.line() + position.line() - 1,
+                if position.line() == 1 {
+                    base.column() + position.column()
+                } else {
+                    position.column()
+                },
+            )
+        });
+
+        let shift = Shift {
+            from: span.end(),
+            to: reparsed.span().end(),
+        };
+        *node = reparsed;
+
+        Some(shift)
+    }
+}
+
+fn shift_span(span: Span, shift: Shift) -> Span {
+    Span::new(shift.position(span.start()), shift.position(span.end()))
+}
+
+fn shift_spans(node: &mut Node, shift: Shift) {
+    map_spans(node, &|position| shift.position(position));
+}
+
+fn map_spans(node: &mut Node, map: &impl Fn(Position) -> Position) {
+    let span = |span: Span| {
+        if span.is_synthetic() {
+            span
+        } else {
+            Span::new(map(span.start()), map(span.end()))
+        }
+    };
+
+    match node {
+        Node::Null(node) => node.span = span(node.span),
+        Node::Boolean(node) => node.span = span(node.span),
+        Node::Number(node) => node.span = span(node.span),
+        Node::String(node) => node.span = span(node.span),
+        Node::Array(array) => {
+            array.span = span(array.span);
+            for item in array.value.iter_mut() {
+                map_spans(item, map);
+            }
+        }
+        Node::Object(object) => {
+            object.span = span(object.span);
+            for (key, value) in object.members_mut() {
+                key.span = span(key.span);
+                map_spans(value, map);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that reparsing after replacing `old` in `source` gives the
+    /// same result as parsing the edited source from scratch.
+    fn assert_reparses(source: &str, old: &str, replacement: &str) {
+        let source = source.to_string();
+        let start = source.find(old).expect("could not find text to replace");
+        let edit = TextEdit::new(start..start + old.len(), replacement);
+        let edited = edit.apply(&source).unwrap();
+
+        let mut parser = Parser::new_with_comments();
+        let previous = parser.parse(&source).unwrap();
+        let expected = Parser::new_with_comments().parse(&edited);
+        let actual = parser.reparse(previous, &edited, &edit);
+
+        assert_eq!(actual, expected, "replacing {:?} in {:?}", old, source);
+    }
+
+    #[test]
+    fn it_reparses_like_a_full_parse() {
+        let source = "{\"a\": [1, 2],\n  \"b\": {\"c\": true}, \"d\": \"x\"\n}";
+        assert_reparses(source, "2", "23");
+        assert_reparses(source, "2", "99999999999999999999");
+        assert_reparses(source, "true", "false,\n    \"e\": [\n null]");
+        assert_reparses(source, "\"x\"", "'y'");
+        assert_reparses(source, "1, 2", "");
+        assert_reparses(source, ",\n  \"b\"", ", \"z\": 1,\n\n  \"b\"");
+
+        // the depth limit applies to the whole document
+        let source = "[[[1]], 2]".to_string();
+        let edit = TextEdit::new(3..4, "[1]");
+        let edited = edit.apply(&source).unwrap();
+        let mut parser = Parser::new_without_comments().max_depth(3);
+        let previous = parser.parse(&source).unwrap();
+        assert_eq!(
+            parser.reparse(previous, &edited, &edit),
+            Err("nesting deeper than 3 levels at line 1 col 4".to_string())
+        );
+
+        // the edited value does not parse on its own, but the document does
+        assert_reparses("[[1], [2]]", "1", "1], [3");
+        assert_reparses("[1, /* a\n b */ 2,\n 3]", "1", "10");
+        assert_reparses("1", "1", "[1.5]");
+    }
+
+    #[test]
+    fn it_reports_the_same_errors_as_a_full_parse() {
+        assert_reparses("[1, 2]", "2", "2]");
+        assert_reparses("[1, 2]", "2", "2 // c");
+        assert_reparses("{\"a\": 1}", "1", "1, \"a\" 2");
+        assert_reparses("{\"a\": 1}", "1", "");
+        assert_reparses("[1, 2]", "2", "}");
+
+        let edit = TextEdit::new(Range { start: 2, end: 1 }, "");
+        assert!(edit.apply("[1, 2]").is_err());
+        let source = "[1]".to_string();
+        let previous = source.parse::<Node>().unwrap();
+        assert!(Parser::new_without_comments()
+            .reparse(previous, &source, &edit)
+            .is_err());
+    }
+}
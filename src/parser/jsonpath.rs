@@ -0,0 +1,1189 @@
+use std::fmt;
+
+use regex::Regex;
+
+use super::node::{Node, Position, Span};
+
+/// An error found while parsing a malformed JSONPath query, together with
+/// the span of the query string that caused it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct QueryError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at column {}",
+            self.message,
+            self.span.start().column() + 1
+        )
+    }
+}
+
+/// A node matched by a JSONPath query, together with its normalized path
+/// (e.g. `$['store']['book'][0]`). The span is available through [`Node::span`].
+#[derive(Debug, PartialEq)]
+pub struct QueryMatch<'a> {
+    pub path: String,
+    pub node: &'a Node,
+}
+
+/// A parsed JSONPath (RFC 9535) query, which can be evaluated against any
+/// number of [`Node`] trees.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice {
+        start: Option<i64>,
+        end: Option<i64>,
+        step: Option<i64>,
+    },
+    Filter(LogicalExpr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum LogicalExpr {
+    Or(Vec<LogicalExpr>),
+    And(Vec<LogicalExpr>),
+    Not(Box<LogicalExpr>),
+    Comparison(Comparable, ComparisonOp, Comparable),
+    Exists(Query),
+    Function(FunctionExpr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ComparisonOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Comparable {
+    Literal(Literal),
+    Query(Query),
+    Function(FunctionExpr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Query {
+    /// `@` queries are relative to the current node, `$` queries to the root.
+    relative: bool,
+    segments: Vec<Segment>,
+}
+
+impl Query {
+    /// A singular query can only ever produce at most one node.
+    fn is_singular(&self) -> bool {
+        self.segments.iter().all(|segment| match segment {
+            Segment::Child(selectors) => {
+                selectors.len() == 1
+                    && matches!(selectors[0], Selector::Name(_) | Selector::Index(_))
+            }
+            Segment::Descendant(_) => false,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FunctionExpr {
+    function: Function,
+    args: Vec<Comparable>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Length,
+    Count,
+    Match,
+    Search,
+    Value,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "length" => Some(Function::Length),
+            "count" => Some(Function::Count),
+            "match" => Some(Function::Match),
+            "search" => Some(Function::Search),
+            "value" => Some(Function::Value),
+            _ => None,
+        }
+    }
+
+    /// Whether the function produces a logical result (usable as a test
+    /// expression) rather than a value (usable in comparisons).
+    fn is_logical(&self) -> bool {
+        matches!(self, Function::Match | Function::Search)
+    }
+}
+
+impl JsonPath {
+    pub fn parse(query: &str) -> Result<JsonPath, QueryError> {
+        let mut parser = QueryParser { query, cursor: 0 };
+
+        if !parser.eat("$") {
+            return Err(parser.error("query must start with `$`", 0));
+        }
+        let segments = parser.parse_segments()?;
+
+        if parser.cursor < query.len() {
+            return Err(parser.error("unexpected character", parser.cursor));
+        }
+
+        Ok(JsonPath { segments })
+    }
+
+    /// Evaluates the query against `root`, returning every matched node in
+    /// document order (object members are visited in unspecified order).
+    pub fn query<'a>(&self, root: &'a Node) -> Vec<QueryMatch<'a>> {
+        let start = vec![QueryMatch {
+            path: "$".to_string(),
+            node: root,
+        }];
+
+        self.segments
+            .iter()
+            .fold(start, |nodes, segment| apply_segment(segment, nodes, root))
+    }
+}
+
+impl Node {
+    /// Parses and evaluates a JSONPath query, e.g. `$.store.book[?@.price < 10].title`.
+    pub fn query(&self, query: &str) -> Result<Vec<QueryMatch<'_>>, QueryError> {
+        Ok(JsonPath::parse(query)?.query(self))
+    }
+}
+
+struct QueryParser<'q> {
+    query: &'q str,
+    cursor: usize,
+}
+
+impl<'q> QueryParser<'q> {
+    fn error(&self, message: &str, start: usize) -> QueryError {
+        let end = self.cursor.max(start + 1).min(self.query.len().max(start));
+        QueryError {
+            message: message.to_string(),
+            span: Span::new(Position::new(start, 1, start), Position::new(end, 1, end)),
+        }
+    }
+
+    fn rest(&self) -> &'q str {
+        &self.query[self.cursor..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.cursor += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.cursor += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), QueryError> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", s), self.cursor))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.cursor += 1;
+        }
+    }
+
+    fn parse_segments(&mut self) -> Result<Vec<Segment>, QueryError> {
+        let mut segments = vec![];
+
+        loop {
+            let before = self.cursor;
+            self.skip_whitespace();
+
+            if self.eat("..") {
+                segments.push(Segment::Descendant(self.parse_segment_selectors(true)?));
+            } else if self.eat(".") {
+                segments.push(Segment::Child(self.parse_segment_selectors(false)?));
+            } else if self.peek() == Some('[') {
+                segments.push(Segment::Child(self.parse_bracketed_selection()?));
+            } else {
+                // whitespace is only allowed between segments, not after them
+                self.cursor = before;
+                return Ok(segments);
+            }
+        }
+    }
+
+    /// Parses what may follow `.` or `..`: a member name shorthand, a
+    /// wildcard or (only after `..`) a bracketed selection.
+    fn parse_segment_selectors(&mut self, descendant: bool) -> Result<Vec<Selector>, QueryError> {
+        match self.peek() {
+            Some('*') => {
+                self.bump();
+                Ok(vec![Selector::Wildcard])
+            }
+            Some('[') if descendant => self.parse_bracketed_selection(),
+            Some(c) if is_name_first(c) => {
+                let start = self.cursor;
+                while let Some(c) = self.peek() {
+                    if !is_name_first(c) && !c.is_ascii_digit() {
+                        break;
+                    }
+                    self.bump();
+                }
+                Ok(vec![Selector::Name(
+                    self.query[start..self.cursor].to_string(),
+                )])
+            }
+            _ => Err(self.error("expected member name or `*`", self.cursor)),
+        }
+    }
+
+    fn parse_bracketed_selection(&mut self) -> Result<Vec<Selector>, QueryError> {
+        self.expect("[")?;
+        let mut selectors = vec![];
+
+        loop {
+            self.skip_whitespace();
+            selectors.push(self.parse_selector()?);
+            self.skip_whitespace();
+
+            if self.eat("]") {
+                return Ok(selectors);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn parse_selector(&mut self) -> Result<Selector, QueryError> {
+        let start = self.cursor;
+
+        match self.peek() {
+            Some('\'' | '"') => Ok(Selector::Name(self.parse_string_literal()?)),
+            Some('*') => {
+                self.bump();
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.bump();
+                self.skip_whitespace();
+                Ok(Selector::Filter(self.parse_logical_or()?))
+            }
+            Some(c) if c == '-' || c == ':' || c.is_ascii_digit() => {
+                let first = self.parse_optional_int()?;
+                self.skip_whitespace();
+                if !self.eat(":") {
+                    return match first {
+                        Some(index) => Ok(Selector::Index(index)),
+                        None => Err(self.error("expected index or slice", start)),
+                    };
+                }
+
+                self.skip_whitespace();
+                let end = self.parse_optional_int()?;
+                self.skip_whitespace();
+                let step = if self.eat(":") {
+                    self.skip_whitespace();
+                    self.parse_optional_int()?
+                } else {
+                    None
+                };
+
+                Ok(Selector::Slice {
+                    start: first,
+                    end,
+                    step,
+                })
+            }
+            _ => Err(self.error("expected selector", start)),
+        }
+    }
+
+    fn parse_optional_int(&mut self) -> Result<Option<i64>, QueryError> {
+        match self.peek() {
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_int().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Parses an integer in the I-JSON range, without leading zeros or `-0`.
+    fn parse_int(&mut self) -> Result<i64, QueryError> {
+        const MAX_SAFE_INTEGER: i64 = 9_007_199_254_740_991;
+
+        let start = self.cursor;
+        self.eat("-");
+        let digits_start = self.cursor;
+        while let Some('0'..='9') = self.peek() {
+            self.bump();
+        }
+
+        let digits = &self.query[digits_start..self.cursor];
+        let raw = &self.query[start..self.cursor];
+        if digits.is_empty() {
+            return Err(self.error("expected integer", start));
+        }
+        if (digits.len() > 1 && digits.starts_with('0')) || raw == "-0" {
+            return Err(self.error("integers must not have leading zeros or be `-0`", start));
+        }
+
+        match raw.parse::<i64>() {
+            Ok(value) if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&value) => Ok(value),
+            _ => Err(self.error("integer is out of range", start)),
+        }
+    }
+
+    fn parse_string_literal(&mut self) -> Result<String, QueryError> {
+        let start = self.cursor;
+        let quote = self.bump().expect("string literal must start with a quote");
+        let mut value = String::new();
+
+        loop {
+            match self.bump() {
+                None => return Err(self.error("unterminated string literal", start)),
+                Some(c) if c == quote => return Ok(value),
+                Some('\\') => value.push(self.parse_escape(quote)?),
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error(
+                        "control characters must be escaped in string literals",
+                        self.cursor - 1,
+                    ))
+                }
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self, quote: char) -> Result<char, QueryError> {
+        let start = self.cursor - 1;
+        match self.bump() {
+            Some('b') => Ok('\u{8}'),
+            Some('f') => Ok('\u{c}'),
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('/') => Ok('/'),
+            Some('\\') => Ok('\\'),
+            Some(c) if c == quote => Ok(c),
+            Some('u') => {
+                let high = self.parse_hex4(start)?;
+                if (0xDC00..0xE000).contains(&high) {
+                    return Err(self.error("unpaired low surrogate", start));
+                }
+                if !(0xD800..0xDC00).contains(&high) {
+                    return char::from_u32(high).ok_or_else(|| self.error("invalid escape", start));
+                }
+
+                if !self.eat("\\u") {
+                    return Err(self.error("unpaired high surrogate", start));
+                }
+                let low = self.parse_hex4(start)?;
+                if !(0xDC00..0xE000).contains(&low) {
+                    return Err(self.error("unpaired high surrogate", start));
+                }
+
+                char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+                    .ok_or_else(|| self.error("invalid escape", start))
+            }
+            _ => Err(self.error("invalid escape", start)),
+        }
+    }
+
+    fn parse_hex4(&mut self, start: usize) -> Result<u32, QueryError> {
+        let digits = self.rest().get(..4).unwrap_or("");
+        if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.error("expected four hexadecimal digits", start));
+        }
+        self.cursor += 4;
+
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+
+    fn parse_logical_or(&mut self) -> Result<LogicalExpr, QueryError> {
+        let mut operands = vec![self.parse_logical_and()?];
+        loop {
+            let before = self.cursor;
+            self.skip_whitespace();
+            if !self.eat("||") {
+                self.cursor = before;
+                break;
+            }
+            self.skip_whitespace();
+            operands.push(self.parse_logical_and()?);
+        }
+
+        Ok(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            LogicalExpr::Or(operands)
+        })
+    }
+
+    fn parse_logical_and(&mut self) -> Result<LogicalExpr, QueryError> {
+        let mut operands = vec![self.parse_basic_expr()?];
+        loop {
+            let before = self.cursor;
+            self.skip_whitespace();
+            if !self.eat("&&") {
+                self.cursor = before;
+                break;
+            }
+            self.skip_whitespace();
+            operands.push(self.parse_basic_expr()?);
+        }
+
+        Ok(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            LogicalExpr::And(operands)
+        })
+    }
+
+    fn parse_basic_expr(&mut self) -> Result<LogicalExpr, QueryError> {
+        let start = self.cursor;
+        let negated = self.eat("!");
+        if negated {
+            self.skip_whitespace();
+        }
+
+        let expr = if self.eat("(") {
+            self.skip_whitespace();
+            let expr = self.parse_logical_or()?;
+            self.skip_whitespace();
+            self.expect(")")?;
+            expr
+        } else {
+            let left = self.parse_comparable()?;
+
+            let before = self.cursor;
+            self.skip_whitespace();
+            match self.parse_comparison_op() {
+                Some(op) => {
+                    if negated {
+                        return Err(
+                            self.error("comparisons cannot be negated without parentheses", start)
+                        );
+                    }
+                    self.skip_whitespace();
+                    let right = self.parse_comparable()?;
+                    self.check_comparable(&left, start)?;
+                    self.check_comparable(&right, start)?;
+                    LogicalExpr::Comparison(left, op, right)
+                }
+                None => {
+                    self.cursor = before;
+                    match left {
+                        Comparable::Query(query) => LogicalExpr::Exists(query),
+                        Comparable::Function(function) if function.function.is_logical() => {
+                            LogicalExpr::Function(function)
+                        }
+                        Comparable::Function(_) => {
+                            return Err(self.error("function result must be compared", start))
+                        }
+                        Comparable::Literal(_) => {
+                            return Err(self.error("literals must be compared", start))
+                        }
+                    }
+                }
+            }
+        };
+
+        Ok(if negated {
+            LogicalExpr::Not(Box::new(expr))
+        } else {
+            expr
+        })
+    }
+
+    fn parse_comparison_op(&mut self) -> Option<ComparisonOp> {
+        // two character operators have to be checked first
+        for (s, op) in [
+            ("==", ComparisonOp::Eq),
+            ("!=", ComparisonOp::Ne),
+            ("<=", ComparisonOp::Le),
+            (">=", ComparisonOp::Ge),
+            ("<", ComparisonOp::Lt),
+            (">", ComparisonOp::Gt),
+        ] {
+            if self.eat(s) {
+                return Some(op);
+            }
+        }
+
+        None
+    }
+
+    /// Verifies that a comparison operand produces a single value.
+    fn check_comparable(&self, comparable: &Comparable, start: usize) -> Result<(), QueryError> {
+        match comparable {
+            Comparable::Query(query) if !query.is_singular() => {
+                Err(self.error("only singular queries can be compared", start))
+            }
+            Comparable::Function(function) if function.function.is_logical() => {
+                Err(self.error("logical function results cannot be compared", start))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn parse_comparable(&mut self) -> Result<Comparable, QueryError> {
+        let start = self.cursor;
+
+        match self.peek() {
+            Some('@') | Some('$') => {
+                let relative = self.bump() == Some('@');
+                Ok(Comparable::Query(Query {
+                    relative,
+                    segments: self.parse_segments()?,
+                }))
+            }
+            Some('\'' | '"') => Ok(Comparable::Literal(Literal::String(
+                self.parse_string_literal()?,
+            ))),
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                Ok(Comparable::Literal(Literal::Number(self.parse_number()?)))
+            }
+            Some(c) if c.is_ascii_lowercase() => {
+                while let Some(c) = self.peek() {
+                    if !c.is_ascii_lowercase() && !c.is_ascii_digit() && c != '_' {
+                        break;
+                    }
+                    self.bump();
+                }
+                let name = &self.query[start..self.cursor];
+
+                if self.peek() != Some('(') {
+                    return match name {
+                        "true" => Ok(Comparable::Literal(Literal::Boolean(true))),
+                        "false" => Ok(Comparable::Literal(Literal::Boolean(false))),
+                        "null" => Ok(Comparable::Literal(Literal::Null)),
+                        _ => Err(self.error("unexpected identifier", start)),
+                    };
+                }
+
+                let function = Function::from_name(name)
+                    .ok_or_else(|| self.error(&format!("unknown function `{}`", name), start))?;
+                Ok(Comparable::Function(
+                    self.parse_function_args(function, start)?,
+                ))
+            }
+            _ => Err(self.error("expected filter expression", start)),
+        }
+    }
+
+    fn parse_function_args(
+        &mut self,
+        function: Function,
+        start: usize,
+    ) -> Result<FunctionExpr, QueryError> {
+        self.expect("(")?;
+        let mut args = vec![];
+
+        self.skip_whitespace();
+        if !self.eat(")") {
+            loop {
+                self.skip_whitespace();
+                // none of the standard functions take a logical argument, so
+                // logical expressions are not accepted as arguments
+                args.push(self.parse_comparable()?);
+                self.skip_whitespace();
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+
+        let arity = match function {
+            Function::Length | Function::Count | Function::Value => 1,
+            Function::Match | Function::Search => 2,
+        };
+        if args.len() != arity {
+            return Err(self.error(
+                &format!(
+                    "function expects {} argument(s), found {}",
+                    arity,
+                    args.len()
+                ),
+                start,
+            ));
+        }
+
+        for arg in &args {
+            let valid = match (function, arg) {
+                // NodesType parameters only accept queries
+                (Function::Count | Function::Value, Comparable::Query(_)) => true,
+                (Function::Count | Function::Value, _) => false,
+                // ValueType parameters accept anything producing a single value
+                (_, Comparable::Query(query)) => query.is_singular(),
+                (_, Comparable::Function(inner)) => !inner.function.is_logical(),
+                (_, Comparable::Literal(_)) => true,
+            };
+            if !valid {
+                return Err(self.error("invalid function argument", start));
+            }
+        }
+
+        Ok(FunctionExpr { function, args })
+    }
+
+    fn parse_number(&mut self) -> Result<f64, QueryError> {
+        let start = self.cursor;
+        self.eat("-");
+
+        let int_start = self.cursor;
+        while let Some('0'..='9') = self.peek() {
+            self.bump();
+        }
+        let int = &self.query[int_start..self.cursor];
+        if int.is_empty() || (int.len() > 1 && int.starts_with('0')) {
+            return Err(self.error("invalid number literal", start));
+        }
+
+        if self.eat(".") {
+            let frac_start = self.cursor;
+            while let Some('0'..='9') = self.peek() {
+                self.bump();
+            }
+            if self.cursor == frac_start {
+                return Err(self.error("invalid number literal", start));
+            }
+        }
+
+        if self.eat("e") || self.eat("E") {
+            if !self.eat("+") {
+                self.eat("-");
+            }
+            let exp_start = self.cursor;
+            while let Some('0'..='9') = self.peek() {
+                self.bump();
+            }
+            if self.cursor == exp_start {
+                return Err(self.error("invalid number literal", start));
+            }
+        }
+
+        self.query[start..self.cursor]
+            .parse()
+            .map_err(|_| self.error("invalid number literal", start))
+    }
+}
+
+fn is_name_first(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+fn apply_segment<'a>(
+    segment: &Segment,
+    nodes: Vec<QueryMatch<'a>>,
+    root: &'a Node,
+) -> Vec<QueryMatch<'a>> {
+    let mut result = vec![];
+
+    for input in nodes {
+        match segment {
+            Segment::Child(selectors) => {
+                for selector in selectors {
+                    apply_selector(selector, &input, root, &mut result);
+                }
+            }
+            Segment::Descendant(selectors) => {
+                let mut descendants = vec![];
+                collect_descendants(input, &mut descendants);
+                for descendant in &descendants {
+                    for selector in selectors {
+                        apply_selector(selector, descendant, root, &mut result);
+                    }
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Collects `node` and all of its descendants in document order (pre-order).
+fn collect_descendants<'a>(node: QueryMatch<'a>, result: &mut Vec<QueryMatch<'a>>) {
+    let children = children_of(&node);
+    result.push(node);
+    for child in children {
+        collect_descendants(child, result);
+    }
+}
+
+fn children_of<'a>(parent: &QueryMatch<'a>) -> Vec<QueryMatch<'a>> {
+    match parent.node {
+        Node::Array(array) => array
+            .value
+            .iter()
+            .enumerate()
+            .map(|(index, node)| QueryMatch {
+                path: format!("{}[{}]", parent.path, index),
+                node,
+            })
+            .collect(),
+        Node::Object(object) => object
+            .entries()
+            .map(|(key, node)| QueryMatch {
+                path: format!("{}[{}]", parent.path, normalized_name(key)),
+                node,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn apply_selector<'a>(
+    selector: &Selector,
+    input: &QueryMatch<'a>,
+    root: &'a Node,
+    result: &mut Vec<QueryMatch<'a>>,
+) {
+    match (selector, input.node) {
+        (Selector::Name(name), Node::Object(object)) => {
+            if let Some(node) = object.get(name) {
+                result.push(QueryMatch {
+                    path: format!("{}[{}]", input.path, normalized_name(name)),
+                    node,
+                });
+            }
+        }
+        (Selector::Wildcard, _) => result.extend(children_of(input)),
+        (Selector::Index(index), Node::Array(array)) => {
+            if let Some(index) = normalize_index(*index, array.value.len()) {
+                result.push(QueryMatch {
+                    path: format!("{}[{}]", input.path, index),
+                    node: &array.value[index],
+                });
+            }
+        }
+        (Selector::Slice { start, end, step }, Node::Array(array)) => {
+            for index in slice_indices(*start, *end, *step, array.value.len()) {
+                result.push(QueryMatch {
+                    path: format!("{}[{}]", input.path, index),
+                    node: &array.value[index],
+                });
+            }
+        }
+        (Selector::Filter(expr), Node::Array(_) | Node::Object(_)) => {
+            for child in children_of(input) {
+                if evaluate_logical(expr, child.node, root) {
+                    result.push(child);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    if index >= 0 && (index as usize) < len {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+/// Computes the selected indices of an array slice as specified by RFC 9535.
+fn slice_indices(
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+    len: usize,
+) -> Vec<usize> {
+    let len = len as i64;
+    let step = step.unwrap_or(1);
+    if step == 0 {
+        return vec![];
+    }
+
+    let normalize = |i: i64| if i >= 0 { i } else { len + i };
+    let mut indices = vec![];
+
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).clamp(0, len);
+        let upper = normalize(end.unwrap_or(len)).clamp(0, len);
+        let mut i = lower;
+        while i < upper {
+            indices.push(i as usize);
+            i += step;
+        }
+    } else {
+        let upper = normalize(start.unwrap_or(len - 1)).clamp(-1, len - 1);
+        let lower = normalize(end.unwrap_or(-len - 1)).clamp(-1, len - 1);
+        let mut i = upper;
+        while lower < i {
+            indices.push(i as usize);
+            i += step;
+        }
+    }
+
+    indices
+}
+
+/// Formats an object member name as a normalized path element, e.g. `'key'`.
+fn normalized_name(name: &str) -> String {
+    let mut result = String::with_capacity(name.len() + 2);
+    result.push('\'');
+    for c in name.chars() {
+        match c {
+            '\u{8}' => result.push_str("\\b"),
+            '\u{c}' => result.push_str("\\f"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\'' => result.push_str("\\'"),
+            '\\' => result.push_str("\\\\"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('\'');
+    result
+}
+
+/// The result of evaluating a comparable within a filter expression.
+enum Value<'a> {
+    Nothing,
+    Node(&'a Node),
+    Literal(&'a Literal),
+    Number(f64),
+}
+
+fn evaluate_logical(expr: &LogicalExpr, current: &Node, root: &Node) -> bool {
+    match expr {
+        LogicalExpr::Or(operands) => operands.iter().any(|e| evaluate_logical(e, current, root)),
+        LogicalExpr::And(operands) => operands.iter().all(|e| evaluate_logical(e, current, root)),
+        LogicalExpr::Not(expr) => !evaluate_logical(expr, current, root),
+        LogicalExpr::Exists(query) => !evaluate_query(query, current, root).is_empty(),
+        LogicalExpr::Function(function) => match function.function {
+            Function::Match | Function::Search => evaluate_regex(function, current, root),
+            // value functions are rejected as test expressions while parsing
+            _ => false,
+        },
+        LogicalExpr::Comparison(left, op, right) => {
+            let left = evaluate_comparable(left, current, root);
+            let right = evaluate_comparable(right, current, root);
+            compare(&left, *op, &right)
+        }
+    }
+}
+
+fn evaluate_query<'a>(query: &Query, current: &'a Node, root: &'a Node) -> Vec<&'a Node> {
+    let start = if query.relative { current } else { root };
+    let nodes = vec![QueryMatch {
+        path: String::new(),
+        node: start,
+    }];
+
+    query
+        .segments
+        .iter()
+        .fold(nodes, |nodes, segment| apply_segment(segment, nodes, root))
+        .into_iter()
+        .map(|m| m.node)
+        .collect()
+}
+
+fn evaluate_comparable<'a>(
+    comparable: &'a Comparable,
+    current: &'a Node,
+    root: &'a Node,
+) -> Value<'a> {
+    match comparable {
+        Comparable::Literal(literal) => Value::Literal(literal),
+        Comparable::Query(query) => match evaluate_query(query, current, root).as_slice() {
+            [node] => Value::Node(node),
+            _ => Value::Nothing,
+        },
+        Comparable::Function(function) => {
+            let arg = &function.args[0];
+            match function.function {
+                Function::Length => match evaluate_comparable(arg, current, root) {
+                    Value::Node(Node::String(node)) => {
+                        Value::Number(node.value.chars().count() as f64)
+                    }
+                    Value::Node(Node::Array(node)) => Value::Number(node.value.len() as f64),
+                    Value::Node(Node::Object(node)) => Value::Number(node.len() as f64),
+                    Value::Literal(Literal::String(s)) => Value::Number(s.chars().count() as f64),
+                    _ => Value::Nothing,
+                },
+                Function::Count => match arg {
+                    Comparable::Query(query) => {
+                        Value::Number(evaluate_query(query, current, root).len() as f64)
+                    }
+                    _ => Value::Nothing,
+                },
+                Function::Value => match arg {
+                    Comparable::Query(query) => {
+                        match evaluate_query(query, current, root).as_slice() {
+                            [node] => Value::Node(node),
+                            _ => Value::Nothing,
+                        }
+                    }
+                    _ => Value::Nothing,
+                },
+                // logical functions are rejected as comparables while parsing
+                Function::Match | Function::Search => Value::Nothing,
+            }
+        }
+    }
+}
+
+fn evaluate_regex(function: &FunctionExpr, current: &Node, root: &Node) -> bool {
+    let string = |value: Value<'_>| -> Option<String> {
+        match value {
+            Value::Node(Node::String(node)) => Some(node.value.clone()),
+            Value::Literal(Literal::String(s)) => Some(s.clone()),
+            _ => None,
+        }
+    };
+
+    let subject = string(evaluate_comparable(&function.args[0], current, root));
+    let pattern = string(evaluate_comparable(&function.args[1], current, root));
+    let (subject, pattern) = match (subject, pattern) {
+        (Some(subject), Some(pattern)) => (subject, pattern),
+        _ => return false,
+    };
+
+    let pattern = translate_iregexp(&pattern);
+    let pattern = if function.function == Function::Match {
+        format!("^(?:{})$", pattern)
+    } else {
+        pattern
+    };
+
+    // an invalid pattern simply does not match anything
+    Regex::new(&pattern)
+        .map(|regex| regex.is_match(&subject))
+        .unwrap_or(false)
+}
+
+/// Translates an I-Regexp (RFC 9485) into the `regex` crate's syntax. The
+/// only difference that matters is that `.` must not match `\n` or `\r`.
+fn translate_iregexp(pattern: &str) -> String {
+    let mut result = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    let mut in_class = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                result.push(c);
+                if let Some(escaped) = chars.next() {
+                    result.push(escaped);
+                }
+            }
+            '[' => {
+                in_class = true;
+                result.push(c);
+            }
+            ']' => {
+                in_class = false;
+                result.push(c);
+            }
+            '.' if !in_class => result.push_str("[^\\n\\r]"),
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// A view of a filter value that can be compared, independent of whether it
+/// came from the document or from a literal in the query.
+enum Comparand<'a> {
+    Nothing,
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(&'a str),
+    Node(&'a Node),
+}
+
+fn comparand<'a>(value: &'a Value<'a>) -> Comparand<'a> {
+    match value {
+        Value::Nothing => Comparand::Nothing,
+        Value::Number(n) => Comparand::Number(*n),
+        Value::Literal(Literal::Null) => Comparand::Null,
+        Value::Literal(Literal::Boolean(b)) => Comparand::Boolean(*b),
+        Value::Literal(Literal::Number(n)) => Comparand::Number(*n),
+        Value::Literal(Literal::String(s)) => Comparand::String(s),
+        Value::Node(node) => node_comparand(node),
+    }
+}
+
+fn node_comparand(node: &Node) -> Comparand<'_> {
+    match node {
+        Node::Null(_) => Comparand::Null,
+        Node::Boolean(node) => Comparand::Boolean(node.value),
+        Node::Number(node) => Comparand::Number(node.value.as_f64()),
+        Node::String(node) => Comparand::String(&node.value),
+        Node::Array(_) | Node::Object(_) => Comparand::Node(node),
+    }
+}
+
+fn compare(left: &Value, op: ComparisonOp, right: &Value) -> bool {
+    let (left, right) = (comparand(left), comparand(right));
+    match op {
+        ComparisonOp::Eq => equal(&left, &right),
+        ComparisonOp::Ne => !equal(&left, &right),
+        ComparisonOp::Lt => less(&left, &right),
+        ComparisonOp::Le => less(&left, &right) || equal(&left, &right),
+        ComparisonOp::Gt => less(&right, &left),
+        ComparisonOp::Ge => less(&right, &left) || equal(&left, &right),
+    }
+}
+
+fn equal(left: &Comparand, right: &Comparand) -> bool {
+    match (left, right) {
+        (Comparand::Nothing, Comparand::Nothing) => true,
+        (Comparand::Null, Comparand::Null) => true,
+        (Comparand::Boolean(a), Comparand::Boolean(b)) => a == b,
+        (Comparand::Number(a), Comparand::Number(b)) => a == b,
+        (Comparand::String(a), Comparand::String(b)) => a == b,
+        (Comparand::Node(a), Comparand::Node(b)) => a.semantic_eq(b),
+        _ => false,
+    }
+}
+
+fn less(left: &Comparand, right: &Comparand) -> bool {
+    match (left, right) {
+        (Comparand::Number(a), Comparand::Number(b)) => a < b,
+        (Comparand::String(a), Comparand::String(b)) => a < b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    fn paths(root: &Node, query: &str) -> Vec<String> {
+        root.query(query)
+            .expect("could not parse query")
+            .into_iter()
+            .map(|m| m.path)
+            .collect()
+    }
+
+    const STORE: &str = "{\"store\": {\"book\": [
+        {\"title\": \"a\", \"price\": 8.95},
+        {\"title\": \"b\", \"price\": 12.99, \"isbn\": \"x\"},
+        {\"title\": \"c\", \"price\": 8}
+    ], \"bicycle\": {\"price\": 19.95}}}";
+
+    #[test]
+    fn it_selects_children_and_descendants() {
+        let root = parse_source(STORE);
+
+        assert_eq!(
+            paths(&root, "$.store.book[1].title"),
+            vec!["$['store']['book'][1]['title']"]
+        );
+        assert_eq!(
+            paths(&root, "$..price"),
+            vec![
+                "$['store']['book'][0]['price']",
+                "$['store']['book'][1]['price']",
+                "$['store']['book'][2]['price']",
+                "$['store']['bicycle']['price']",
+            ]
+        );
+        assert_eq!(
+            paths(&root, "$.store.book[-1:0:-1]"),
+            vec!["$['store']['book'][2]", "$['store']['book'][1]"]
+        );
+        assert_eq!(
+            paths(&root, "$.store.book[::2]"),
+            vec!["$['store']['book'][0]", "$['store']['book'][2]"]
+        );
+        assert_eq!(
+            paths(&root, "$['store'].*"),
+            vec!["$['store']['book']", "$['store']['bicycle']"]
+        );
+    }
+
+    #[test]
+    fn it_filters() {
+        let root = parse_source(STORE);
+
+        let titles: Vec<_> = root
+            .query("$.store.book[?@.price < 10].title")
+            .unwrap()
+            .into_iter()
+            .map(|m| m.node.span().start().cursor())
+            .collect();
+        assert_eq!(titles.len(), 2);
+
+        assert_eq!(
+            paths(&root, "$.store.book[?@.isbn]"),
+            vec!["$['store']['book'][1]"]
+        );
+        assert_eq!(
+            paths(&root, "$.store.book[?!@.isbn && @.price >= 8.95]").len(),
+            1
+        );
+        assert_eq!(
+            paths(&root, "$.store.book[?match(@.title, '[ab]')]").len(),
+            2
+        );
+        assert_eq!(
+            paths(&root, "$.store[?length(@) == 3]"),
+            vec!["$['store']['book']"]
+        );
+    }
+
+    #[test]
+    fn it_reports_malformed_queries() {
+        let error = JsonPath::parse("$.store[?@.price <]").unwrap_err();
+        assert_eq!(error.span.start().cursor(), 18);
+
+        assert!(JsonPath::parse("store").is_err());
+        assert!(JsonPath::parse("$[01]").is_err());
+        assert!(JsonPath::parse("$[?@.* == 1]").is_err());
+        assert!(JsonPath::parse("$[?length(@)]").is_err());
+    }
+}
@@ -0,0 +1,200 @@
+use super::located;
+use super::node::Span;
+use super::tokenizer::{Token, TokenKind};
+
+/// Caps on the resources parsing a document may take, e.g. for untrusted
+/// input. Every limit is off by default; see [`Parser::max_depth`] for the
+/// nesting depth, which is limited by default.
+///
+/// [`Parser::max_depth`]: super::Parser::max_depth
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Limits {
+    /// In bytes.
+    pub max_input_size: Option<usize>,
+    /// Counting significant tokens, i.e. not whitespace or comments.
+    pub max_tokens: Option<usize>,
+    /// In bytes, without the quotes. This applies to keys as well.
+    pub max_string_length: Option<usize>,
+    /// In bytes.
+    pub max_number_length: Option<usize>,
+    /// Counting duplicate keys as well.
+    pub max_object_entries: Option<usize>,
+    pub max_array_length: Option<usize>,
+}
+
+impl Limits {
+    pub fn with_max_input_size(mut self, bytes: usize) -> Limits {
+        self.max_input_size = Some(bytes);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, tokens: usize) -> Limits {
+        self.max_tokens = Some(tokens);
+        self
+    }
+
+    pub fn with_max_string_length(mut self, bytes: usize) -> Limits {
+        self.max_string_length = Some(bytes);
+        self
+    }
+
+    pub fn with_max_number_length(mut self, bytes: usize) -> Limits {
+        self.max_number_length = Some(bytes);
+        self
+    }
+
+    pub fn with_max_object_entries(mut self, entries: usize) -> Limits {
+        self.max_object_entries = Some(entries);
+        self
+    }
+
+    pub fn with_max_array_length(mut self, items: usize) -> Limits {
+        self.max_array_length = Some(items);
+        self
+    }
+
+    /// Checks the size of the whole input, pointing at the first byte beyond
+    /// the limit.
+    pub(crate) fn check_input(&self, source: &[u8]) -> Result<(), String> {
+        match self.max_input_size {
+            Some(max) if source.len() > max => {
+                let (line, column) = byte_position(&source[..max]);
+                Err(format!(
+                    "input longer than {} bytes at line {} col {}",
+                    max,
+                    line,
+                    column + 1
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the `count`th significant token of the input.
+    pub(crate) fn check_token(&self, token: &Token, count: usize) -> Result<(), String> {
+        let span = token.span();
+        let length = token.raw().len();
+
+        match (self.max_tokens, token.kind()) {
+            (Some(max), _) if count > max => {
+                Err(located(format!("more than {} tokens", max), span))
+            }
+            (_, TokenKind::StringLiteral) => match self.max_string_length {
+                // the quotes do not count
+                Some(max) if length - 2 > max => {
+                    Err(located(format!("string longer than {} bytes", max), span))
+                }
+                _ => Ok(()),
+            },
+            (_, TokenKind::NumberLiteral) => match self.max_number_length {
+                Some(max) if length > max => Err(located(
+                    format!("number literal longer than {} bytes", max),
+                    span,
+                )),
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the number of members of an object or items of an array, as
+    /// the `count`th one at `span` is read.
+    pub(crate) fn check_entries(
+        &self,
+        object: bool,
+        count: usize,
+        span: &Span,
+    ) -> Result<(), String> {
+        match (object, self.max_object_entries, self.max_array_length) {
+            (true, Some(max), _) if count > max => Err(located(
+                format!("object with more than {} entries", max),
+                span,
+            )),
+            (false, _, Some(max)) if count > max => {
+                Err(located(format!("array with more than {} items", max), span))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The line and (zero based) column at the end of `bytes`.
+pub(crate) fn byte_position(bytes: &[u8]) -> (usize, usize) {
+    let line_start = bytes
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |i| i + 1);
+    let lines = bytes.iter().filter(|byte| **byte == b'\n').count();
+
+    (lines + 1, bytes.len() - line_start)
+}
+
+/// The line and column at the end of `bytes`, which start at the given line
+/// and column, e.g. for input read in chunks.
+pub(crate) fn byte_position_after((line, column): (usize, usize), bytes: &[u8]) -> (usize, usize) {
+    match byte_position(bytes) {
+        (1, length) => (line, column + length),
+        (lines, column) => (line + lines - 1, column),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn it_reports_exceeded_limits() {
+        let source = "{\"name\": \"abcdef\",\n \"list\": [1, 2, 3], \"n\": 12345.5}";
+        let cases = [
+            (
+                Limits::default().with_max_input_size(20),
+                "input longer than 20 bytes at line 2 col 2",
+            ),
+            (
+                Limits::default().with_max_tokens(10),
+                "more than 10 tokens at line 2 col 14",
+            ),
+            (
+                Limits::default().with_max_string_length(5),
+                "string longer than 5 bytes at line 1 col 10",
+            ),
+            (
+                Limits::default().with_max_number_length(6),
+                "number literal longer than 6 bytes at line 2 col 26",
+            ),
+            (
+                Limits::default().with_max_object_entries(2),
+                "object with more than 2 entries at line 2 col 21",
+            ),
+            (
+                Limits::default().with_max_array_length(2),
+                "array with more than 2 items at line 2 col 17",
+            ),
+        ];
+
+        for (limits, expected) in cases {
+            let mut parser = Parser::new_without_comments().limits(limits);
+            assert_eq!(parser.parse(source), Err(expected.to_string()));
+            assert_eq!(
+                parser.parse_tape(source).unwrap_err(),
+                expected,
+                "parsing a tape with {:?}",
+                limits
+            );
+            assert_eq!(
+                parser.parse_reader(source.as_bytes()),
+                Err(expected.to_string())
+            );
+        }
+
+        let limits = Limits::default()
+            .with_max_input_size(source.len())
+            .with_max_string_length(6)
+            .with_max_array_length(3);
+        assert!(Parser::new_without_comments()
+            .limits(limits)
+            .parse(source)
+            .is_ok());
+    }
+}
@@ -0,0 +1,152 @@
+use super::node::{Node, Span, StringNode};
+use super::pointer;
+
+/// Whether a located offset lies on an object key or on a value.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Target {
+    Key,
+    Value,
+}
+
+/// The result of locating a source offset within a [`Node`] tree.
+#[derive(Debug)]
+pub struct NodeAt<'a> {
+    /// The innermost node containing the offset. When the offset lies on an
+    /// object key, this is the value belonging to that key.
+    pub node: &'a Node,
+    pub target: Target,
+    /// The key node, if the offset lies on an object key.
+    pub key: Option<&'a StringNode>,
+    /// The ancestors of `node`, from the root down to its direct parent.
+    pub ancestors: Vec<&'a Node>,
+    /// The unescaped JSON Pointer reference tokens leading to `node`.
+    pub tokens: Vec<String>,
+}
+
+impl<'a> NodeAt<'a> {
+    /// The JSON Pointer of the located node.
+    pub fn pointer(&self) -> String {
+        pointer::format(&self.tokens)
+    }
+
+    /// The span of whatever the offset lies on, i.e. the key or the node.
+    pub fn span(&self) -> &'a Span {
+        match self.key {
+            Some(key) => &key.span,
+            None => self.node.span(),
+        }
+    }
+}
+
+impl Node {
+    /// Finds the innermost node at the given source offset (cursor), also
+    /// resolving whether the offset lies on an object key.
+    pub fn node_at(&self, cursor: usize) -> Option<NodeAt<'_>> {
+        self.locate(&|span| span.contains(cursor))
+    }
+
+    /// Like [`Node::node_at`], but locates by line and column and only
+    /// returns the JSON Pointer of the found node. As in a [`Position`], lines
+    /// start at one and columns at zero, and columns count bytes. Editors
+    /// usually report columns in characters or UTF-16 code units, which have
+    /// to be converted (using the line's text) first.
+    ///
+    /// [`Position`]: super::node::Position
+    pub fn path_at(&self, line: usize, column: usize) -> Option<String> {
+        self.locate(&|span| span.contains_position(line, column))
+            .map(|at| at.pointer())
+    }
+
+    fn locate(&self, contains: &dyn Fn(&Span) -> bool) -> Option<NodeAt<'_>> {
+        if !contains(self.span()) {
+            return None;
+        }
+
+        let mut at = NodeAt {
+            node: self,
+            target: Target::Value,
+            key: None,
+            ancestors: vec![],
+            tokens: vec![],
+        };
+
+        'descend: loop {
+            match at.node {
+                Node::Array(array) => {
+                    for (index, child) in array.value.iter().enumerate() {
+                        if contains(child.span()) {
+                            at.ancestors.push(at.node);
+                            at.tokens.push(index.to_string());
+                            at.node = child;
+                            continue 'descend;
+                        }
+                    }
+                }
+                Node::Object(object) => {
+                    for (key_node, child) in object.members() {
+                        let on_key = contains(&key_node.span);
+
+                        if on_key || contains(child.span()) {
+                            at.ancestors.push(at.node);
+                            at.tokens.push(key_node.value.clone());
+                            at.node = child;
+
+                            if on_key {
+                                at.target = Target::Key;
+                                at.key = Some(key_node);
+                                break;
+                            }
+                            continue 'descend;
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            return Some(at);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    #[test]
+    fn it_finds_values_and_keys() {
+        let root = parse_source("{\"a\": {\"b\": [1, 2]}}");
+
+        let at = root.node_at(16).unwrap();
+        assert_eq!(at.pointer(), "/a/b/1");
+        assert_eq!(at.target, Target::Value);
+        assert_eq!(at.ancestors.len(), 3);
+        assert!(std::ptr::eq(at.ancestors[0], &root));
+
+        let at = root.node_at(8).unwrap();
+        assert_eq!(at.pointer(), "/a/b");
+        assert_eq!(at.target, Target::Key);
+        assert_eq!(at.key.unwrap().value, "b");
+        assert_eq!(at.span().start().cursor(), 7);
+
+        let at = root.node_at(10).unwrap();
+        assert_eq!(at.pointer(), "/a");
+        assert_eq!(at.target, Target::Value);
+
+        assert!(root.node_at(100).is_none());
+    }
+
+    #[test]
+    fn it_finds_paths_by_line_and_column() {
+        let root = parse_source("{\n  \"a\": [\n    true\n  ]\n}");
+
+        assert_eq!(root.path_at(3, 5).unwrap(), "/a/0");
+        assert_eq!(root.path_at(2, 2).unwrap(), "/a");
+        assert_eq!(root.path_at(9, 0), None);
+
+        // columns count bytes, and `é` takes two
+        let root = parse_source("{\"é\": [true]}");
+        assert_eq!(root.path_at(1, 8).unwrap(), "/é/0");
+        assert_eq!(root.path_at(1, 7).unwrap(), "/é");
+    }
+}
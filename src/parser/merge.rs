@@ -0,0 +1,157 @@
+use super::node::{detached, Node, StringNode};
+
+/// Applies a JSON Merge Patch (RFC 7396) to `target`: `null` members of the
+/// patch remove members, objects are merged recursively and everything else
+/// replaces the target value.
+///
+/// Surviving nodes keep their spans, while values taken from the patch get
+/// synthetic spans.
+pub fn merge_patch(target: &mut Node, patch: &Node) {
+    merge(target, patch);
+}
+
+/// Computes a merge patch that turns `from` into `to`, i.e. applying it to
+/// `from` with [`merge_patch`] results in a value equal to `to`.
+///
+/// Since `null` members of a merge patch delete members, this can not
+/// express object members that are `null` in `to`; applying the patch
+/// removes them instead.
+pub fn diff(from: &Node, to: &Node) -> Node {
+    // equal objects result in an empty patch, while any other `to` has to
+    // replace the target as a whole
+    match (from, to) {
+        (Node::Object(from), Node::Object(to)) => {
+            let mut members = vec![];
+
+            for (key, _) in from.entries() {
+                if !to.contains_key(key) {
+                    members.push((key.clone(), Node::null()));
+                }
+            }
+
+            for (key, node) in to.entries() {
+                match from.get(key) {
+                    Some(old) if old.semantic_eq(node) => {}
+                    Some(old) => members.push((key.clone(), diff(old, node))),
+                    None => members.push((key.clone(), detached(node))),
+                }
+            }
+
+            Node::object(members)
+        }
+        _ => detached(to),
+    }
+}
+
+/// Merges `patch` into `target`.
+fn merge(target: &mut Node, patch: &Node) {
+    let patch = match patch {
+        Node::Object(patch) => patch,
+        _ => {
+            // an equal value keeps its span
+            if !target.semantic_eq(patch) {
+                *target = detached(patch);
+            }
+            return;
+        }
+    };
+
+    if !target.is_object() {
+        *target = Node::object(vec![]);
+    }
+
+    let object = match target {
+        Node::Object(object) => object,
+        _ => return,
+    };
+
+    for (key, value) in patch.entries() {
+        if value.is_null() {
+            object.remove(key);
+            continue;
+        }
+
+        match object.get_mut(key) {
+            Some(member) => merge(member, value),
+            None => {
+                let mut member = Node::null();
+                merge(&mut member, value);
+                object.insert(StringNode::synthetic(key), member);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    #[test]
+    fn it_merges_patches() {
+        let mut target = parse_source(
+            "{\"title\": \"Goodbye!\", \"author\": {\"givenName\": \"John\", \"familyName\": \"Doe\"}, \"tags\": [\"example\", \"sample\"], \"content\": \"This will be unchanged\"}",
+        );
+        let patch = parse_source(
+            "{\"title\": \"Hello!\", \"phoneNumber\": \"+01-123-456-7890\", \"author\": {\"familyName\": null}, \"tags\": [\"example\"], \"extra\": {\"a\": null, \"b\": 1}}",
+        );
+
+        merge_patch(&mut target, &patch);
+        assert!(target.semantic_eq(&parse_source(
+                "{\"title\": \"Hello!\", \"author\": {\"givenName\": \"John\"}, \"tags\": [\"example\"], \"content\": \"This will be unchanged\", \"phoneNumber\": \"+01-123-456-7890\", \"extra\": {\"b\": 1}}"
+            )));
+
+        // untouched members keep their spans, replaced ones are synthesized
+        let content = target.pointer("/content").unwrap();
+        assert_eq!(content.span().start().cursor(), 118);
+        assert!(target.pointer("/title").unwrap().span().is_synthetic());
+        assert_eq!(
+            target.pointer("/author").unwrap().raw(),
+            "{\"givenName\":\"John\"}"
+        );
+
+        let mut target = parse_source("[1, 2]");
+        merge_patch(&mut target, &parse_source("{\"a\": {\"b\": null}}"));
+        assert_eq!(target.raw(), "{\"a\":{}}");
+    }
+
+    #[test]
+    fn it_keeps_unchanged_objects() {
+        let source = "{\"a\": {\"b\":  1}}";
+        let mut target = parse_source(source);
+        merge_patch(
+            &mut target,
+            &parse_source("{\"a\": {\"b\": 1, \"c\": null}}"),
+        );
+        assert_eq!(target.raw_in(source), Some(source));
+        assert_eq!(target.pointer("/a/b").unwrap().raw_in(source), Some("1"));
+    }
+
+    #[test]
+    fn it_computes_merge_patches() {
+        let from = parse_source("{\"a\": {\"b\": 1, \"c\": 2}, \"d\": [1], \"e\": true}");
+        let to = parse_source("{\"a\": {\"b\": 1, \"c\": 3}, \"d\": [1, 2], \"f\": \"x\"}");
+
+        let patch = diff(&from, &to);
+        assert_eq!(
+            patch.raw(),
+            "{\"e\":null,\"a\":{\"c\":3},\"d\":[1,2],\"f\":\"x\"}"
+        );
+
+        let mut merged = from.clone();
+        merge_patch(&mut merged, &patch);
+        assert!(merged.semantic_eq(&to));
+
+        assert_eq!(diff(&to, &to).raw(), "{}");
+    }
+
+    #[test]
+    fn it_round_trips_equal_documents() {
+        for source in ["1", "\"a\"", "null", "[1, {\"a\": 2}]", "{\"a\": [1]}"] {
+            let from = parse_source(source);
+            let mut merged = from.clone();
+            merge_patch(&mut merged, &diff(&from, &from));
+            assert!(merged.semantic_eq(&from), "merging into {:?}", source);
+        }
+    }
+}
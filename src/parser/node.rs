@@ -1,5 +1,6 @@
 use core::fmt::Debug;
 use regex::Regex;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 // const NEWLINE_SPLIT_PATTERN: Regex = Regex::new("(\n\r|\n)").unwrap();
@@ -117,7 +118,7 @@ impl Position {
         // FIXME: This never changes but can not be stored, compiled, as a constant.
         //        We should probably consider moving this into the Tokenizer instance.
         let split_regex = Regex::new(NEWLINE_SPLIT_PATTERN).unwrap();
-        let lines: Vec<_> = split_regex.split(s).into_iter().collect();
+        let lines: Vec<_> = split_regex.split(s).collect();
 
         if lines.len() == 1 {
             self.add_columns(lines.first().unwrap().len());
@@ -145,8 +146,9 @@ impl Position {
         // we're modifying self.column directly, as to bypass
         // self.add_columns's call to self.add_cursor. The reason
         // being that we've already advanced cursor manually
-        // with the full string (s) length.
-        self.column += last_line.len();
+        // with the full string (s) length. The last line starts
+        // at column zero, like after any other line break.
+        self.column = last_line.len();
 
         // TODO: 1. split string by lines
         //       2. advance self by len of each substring plus a new line per substring
@@ -205,6 +207,25 @@ impl Span {
         }
     }
 
+    /// The span of a node that was not parsed from any source, e.g. one that
+    /// was synthesized by a transform. Its positions are on the (otherwise
+    /// impossible) line zero, so it never contains any offset or position.
+    pub fn synthetic() -> Span {
+        let position = Position {
+            cursor: 0,
+            line: 0,
+            column: 0,
+        };
+        Span {
+            start: position,
+            end: position,
+        }
+    }
+
+    pub fn is_synthetic(&self) -> bool {
+        self.start.line == 0
+    }
+
     /// A Span collapsed at the given position.
     pub fn collapsed(position: Position) -> Span {
         Span {
@@ -233,6 +254,20 @@ impl Span {
     pub fn end(&self) -> Position {
         self.end
     }
+
+    /// Whether the given source offset (cursor) lies within this span.
+    /// The start is inclusive and the end is exclusive.
+    pub fn contains(&self, cursor: usize) -> bool {
+        self.start.cursor <= cursor && cursor < self.end.cursor
+    }
+
+    /// Like [`Span::contains`], but compares by line and (zero based, byte)
+    /// column, for when only those are known.
+    pub fn contains_position(&self, line: usize, column: usize) -> bool {
+        let position = (line, column);
+        (self.start.line, self.start.column) <= position
+            && position < (self.end.line, self.end.column)
+    }
 }
 
 // TODO: what do we name this?
@@ -244,7 +279,7 @@ impl Span {
 //     fn span(&self) -> &Span;
 // }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Node {
     Null(NullNode),
     Boolean(BooleanNode),
@@ -255,95 +290,375 @@ pub enum Node {
 }
 
 impl Node {
-    pub fn is_null(&self) -> bool {
+    pub fn span(&self) -> &Span {
         match self {
-            Node::Null(_) => true,
-            _ => false,
+            Node::Null(node) => &node.span,
+            Node::Boolean(node) => &node.span,
+            Node::Number(node) => &node.span,
+            Node::String(node) => &node.span,
+            Node::Array(node) => &node.span,
+            Node::Object(node) => &node.span,
         }
     }
 
-    pub fn is_boolean(&self) -> bool {
+    /// The raw text of a scalar. Containers do not keep theirs, which would
+    /// copy the source once per nesting level, so it is rendered compactly
+    /// from their members instead; see [`Node::raw_in`] for the source text.
+    pub fn raw(&self) -> Cow<'_, str> {
         match self {
-            Node::Boolean(_) => true,
-            _ => false,
+            Node::Null(node) => Cow::Borrowed(&node.raw),
+            Node::Boolean(node) => Cow::Borrowed(&node.raw),
+            Node::Number(node) => Cow::Borrowed(&node.raw),
+            Node::String(node) => Cow::Borrowed(&node.raw),
+            Node::Array(_) | Node::Object(_) => {
+                let mut raw = String::new();
+                write_raw(self, &mut raw);
+                Cow::Owned(raw)
+            }
         }
     }
 
-    pub fn is_number(&self) -> bool {
-        match self {
-            Node::Number(_) => true,
-            _ => false,
+    /// The text of this node in `source`, the document it was parsed from,
+    /// or `None` for synthesized nodes.
+    pub fn raw_in<'s>(&self, source: &'s str) -> Option<&'s str> {
+        let span = self.span();
+        if span.is_synthetic() {
+            return None;
         }
+
+        source.get(span.start().cursor()..span.end().cursor())
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Node::Null(_))
+    }
+
+    pub fn is_boolean(&self) -> bool {
+        matches!(self, Node::Boolean(_))
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, Node::Number(_))
     }
 
     pub fn is_string(&self) -> bool {
-        match self {
-            Node::String(_) => true,
-            _ => false,
-        }
+        matches!(self, Node::String(_))
     }
 
     pub fn is_array(&self) -> bool {
-        match self {
-            Node::Array(_) => true,
-            _ => false,
-        }
+        matches!(self, Node::Array(_))
     }
 
     pub fn is_object(&self) -> bool {
-        match self {
-            Node::Object(_) => true,
-            _ => false,
-        }
+        matches!(self, Node::Object(_))
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct NullNode {
     pub span: Span,
     pub raw: String, // this will always be "null"
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct BooleanNode {
     pub span: Span,
     pub value: bool,
     pub raw: String, // this will always be either "false" or "true"
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct NumberNode {
     pub span: Span,
     pub value: NumberNodeValue,
     pub raw: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum NumberNodeValue {
     Float(f64),
     Int(i64),
 }
 
-#[derive(Debug, PartialEq)]
+impl NumberNodeValue {
+    /// Integers are converted to floats, which may lose precision.
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            NumberNodeValue::Int(value) => *value as f64,
+            NumberNodeValue::Float(value) => *value,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct StringNode {
     pub span: Span,
     pub value: String,
     pub raw: String, // this includes the quotes ("")
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct ArrayNode {
     pub span: Span,
     pub value: Vec<Node>, // TODO: does this need to be Box<Node>?
-    pub raw: String,      // this includes the square brackets ([])
 }
 
-#[derive(Debug, PartialEq)]
+/// An object, with its members in insertion order. Parsed objects insert
+/// their members in source order.
+#[derive(Debug, PartialEq, Clone)]
 pub struct ObjectNode {
     pub span: Span,
-    // pub value: Vec<ObjectEntry>, // TODO: does this need to be Box<Node>?
-    pub value: HashMap<String, Node>, // TODO: this will be String for now
-    pub raw: String,                  // this includes the curly braces ({})
+    // the key nodes (with spans) and values, in insertion order
+    members: Vec<(StringNode, Node)>,
+    // the position of each key in `members`
+    index: HashMap<String, usize>,
+}
+
+impl ObjectNode {
+    /// An object without members, at `span`.
+    pub(crate) fn empty(span: Span) -> ObjectNode {
+        ObjectNode {
+            span,
+            members: vec![],
+            index: HashMap::new(),
+        }
+    }
+
+    /// Inserts a member with the given key node, returning the value it
+    /// replaced. A new member comes after all others, a replaced one keeps its
+    /// position (but takes the new key node).
+    pub fn insert(&mut self, key: StringNode, value: Node) -> Option<Node> {
+        match self.index.get(&key.value) {
+            Some(&position) => {
+                let (_, replaced) = std::mem::replace(&mut self.members[position], (key, value));
+                Some(replaced)
+            }
+            None => {
+                self.index.insert(key.value.clone(), self.members.len());
+                self.members.push((key, value));
+                None
+            }
+        }
+    }
+
+    /// Removes the member with the given key, returning its value. The members
+    /// after it keep their order.
+    pub fn remove(&mut self, key: &str) -> Option<Node> {
+        let position = self.index.remove(key)?;
+        let (_, removed) = self.members.remove(position);
+        for (key, _) in &self.members[position..] {
+            if let Some(index) = self.index.get_mut(&key.value) {
+                *index -= 1;
+            }
+        }
+        Some(removed)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        self.index
+            .get(key)
+            .map(|&position| &self.members[position].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Node> {
+        match self.index.get(key) {
+            Some(&position) => Some(&mut self.members[position].1),
+            None => None,
+        }
+    }
+
+    /// The key node of the member with the given key, e.g. for its span.
+    pub fn key(&self, key: &str) -> Option<&StringNode> {
+        self.index
+            .get(key)
+            .map(|&position| &self.members[position].0)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// The keys and values of the members of this object, in order.
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = (&String, &Node)> {
+        self.members.iter().map(|(key, value)| (&key.value, value))
+    }
+
+    /// Like [`ObjectNode::entries`], but with the key nodes.
+    pub fn members(&self) -> impl DoubleEndedIterator<Item = (&StringNode, &Node)> {
+        self.members.iter().map(|(key, value)| (key, value))
+    }
+
+    /// The key nodes and values of the members of this object, in order, for
+    /// updating their spans. Changing the value of a key node is a bug.
+    pub(crate) fn members_mut(&mut self) -> impl Iterator<Item = (&mut StringNode, &mut Node)> {
+        self.members.iter_mut().map(|(key, value)| (key, value))
+    }
+
+    /// Takes the members out of this object, in order.
+    pub fn into_members(self) -> impl Iterator<Item = (StringNode, Node)> {
+        self.members.into_iter()
+    }
+}
+
+// Constructors for synthesized nodes. These have a synthetic span and their
+// `raw` is the compact JSON representation of their value.
+impl Node {
+    pub fn null() -> Node {
+        Node::Null(NullNode {
+            span: Span::synthetic(),
+            raw: "null".to_string(),
+        })
+    }
+
+    pub fn boolean(value: bool) -> Node {
+        Node::Boolean(BooleanNode {
+            span: Span::synthetic(),
+            value,
+            raw: value.to_string(),
+        })
+    }
+
+    pub fn int(value: i64) -> Node {
+        Node::Number(NumberNode {
+            span: Span::synthetic(),
+            value: NumberNodeValue::Int(value),
+            raw: value.to_string(),
+        })
+    }
+
+    /// The `raw` of a finite value is in plain decimal notation and always
+    /// has a fraction (`1.0`, never `1e300`). Non-finite values are allowed,
+    /// but their `raw` (`NaN`, `inf`, `-inf`) is not JSON.
+    pub fn float(value: f64) -> Node {
+        let mut raw = value.to_string();
+        if value.is_finite() && !raw.contains('.') {
+            raw.push_str(".0");
+        }
+
+        Node::Number(NumberNode {
+            span: Span::synthetic(),
+            value: NumberNodeValue::Float(value),
+            raw,
+        })
+    }
+
+    pub fn string(value: &str) -> Node {
+        Node::String(StringNode::synthetic(value))
+    }
+
+    pub fn array(value: Vec<Node>) -> Node {
+        Node::Array(ArrayNode {
+            span: Span::synthetic(),
+            value,
+        })
+    }
+
+    /// Members keep the order of `entries`. Later entries with the same key
+    /// replace the value of earlier ones.
+    pub fn object(entries: Vec<(String, Node)>) -> Node {
+        let mut object = ObjectNode::empty(Span::synthetic());
+        for (key, node) in entries {
+            object.insert(StringNode::synthetic(&key), node);
+        }
+
+        Node::Object(object)
+    }
+}
+
+impl StringNode {
+    pub fn synthetic(value: &str) -> StringNode {
+        StringNode {
+            span: Span::synthetic(),
+            value: value.to_string(),
+            raw: quote_string(value),
+        }
+    }
+}
+
+/// A copy of `node` with synthetic spans, for inserting it elsewhere.
+pub(crate) fn detached(node: &Node) -> Node {
+    let mut node = node.clone();
+    detach(&mut node);
+    node
+}
+
+fn detach(node: &mut Node) {
+    match node {
+        Node::Null(node) => node.span = Span::synthetic(),
+        Node::Boolean(node) => node.span = Span::synthetic(),
+        Node::Number(node) => node.span = Span::synthetic(),
+        Node::String(node) => node.span = Span::synthetic(),
+        Node::Array(array) => {
+            array.span = Span::synthetic();
+            array.value.iter_mut().for_each(detach);
+        }
+        Node::Object(object) => {
+            object.span = Span::synthetic();
+            for (key, value) in &mut object.members {
+                key.span = Span::synthetic();
+                detach(value);
+            }
+        }
+    }
+}
+
+/// Quotes and escapes `value` as a JSON string literal.
+pub fn quote_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\u{8}' => result.push_str("\\b"),
+            '\u{c}' => result.push_str("\\f"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Appends the compact raw text of `node` to `output`, with object members
+/// in order.
+fn write_raw(node: &Node, output: &mut String) {
+    match node {
+        Node::Array(array) => {
+            output.push('[');
+            for (index, item) in array.value.iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                write_raw(item, output);
+            }
+            output.push(']');
+        }
+        Node::Object(object) => {
+            output.push('{');
+            for (index, (key, member)) in object.members().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                output.push_str(&key.raw);
+                output.push(':');
+                write_raw(member, output);
+            }
+            output.push('}');
+        }
+        node => output.push_str(&node.raw()),
+    }
 }
 
 // // TODO: is this a node?
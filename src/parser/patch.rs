@@ -0,0 +1,587 @@
+use std::fmt;
+
+use super::node::{detached, Node, Span, StringNode};
+use super::pointer::{self, parse_index};
+use super::visit::{Path, PathSegment};
+
+/// A single JSON Patch (RFC 6902) operation. Pointers are kept in their
+/// escaped form, e.g. `/a~1b/0`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operation {
+    Add { path: String, value: Node },
+    Remove { path: String },
+    Replace { path: String, value: Node },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Node },
+}
+
+impl Operation {
+    /// The name of this operation as used in the `op` member.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Add { .. } => "add",
+            Operation::Remove { .. } => "remove",
+            Operation::Replace { .. } => "replace",
+            Operation::Move { .. } => "move",
+            Operation::Copy { .. } => "copy",
+            Operation::Test { .. } => "test",
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
+            Operation::Add { path, .. }
+            | Operation::Remove { path }
+            | Operation::Replace { path, .. }
+            | Operation::Move { path, .. }
+            | Operation::Copy { path, .. }
+            | Operation::Test { path, .. } => path,
+        }
+    }
+
+    /// The (synthesized) JSON representation of this operation.
+    pub fn to_node(&self) -> Node {
+        let mut members = vec![
+            ("op".to_string(), Node::string(self.name())),
+            ("path".to_string(), Node::string(self.path())),
+        ];
+
+        match self {
+            Operation::Add { value, .. }
+            | Operation::Replace { value, .. }
+            | Operation::Test { value, .. } => members.push(("value".to_string(), detached(value))),
+            Operation::Move { from, .. } | Operation::Copy { from, .. } => {
+                members.push(("from".to_string(), Node::string(from)))
+            }
+            Operation::Remove { .. } => {}
+        }
+
+        Node::object(members)
+    }
+}
+
+/// An error in a patch document, or a failed operation.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PatchError {
+    pub message: String,
+    /// The index of the offending operation within the patch.
+    pub index: Option<usize>,
+    /// The pointer (`path` or `from`) the failing operation referred to.
+    pub pointer: Option<String>,
+    /// Where the offending operation (or part of it) is in the patch source.
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        if let Some(index) = self.index {
+            write!(f, " in operation {}", index)?;
+        }
+
+        if let Some(pointer) = &self.pointer {
+            write!(f, " at `{}`", pointer)?;
+        }
+
+        if let Some(span) = self.span {
+            write!(
+                f,
+                ", line {} col {}",
+                span.start().line(),
+                span.start().column() + 1
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A JSON Patch (RFC 6902) document.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Patch {
+    operations: Vec<Operation>,
+    // the span of each operation in the patch source
+    spans: Vec<Span>,
+}
+
+impl Patch {
+    pub fn new(operations: Vec<Operation>) -> Patch {
+        Patch {
+            spans: vec![Span::synthetic(); operations.len()],
+            operations,
+        }
+    }
+
+    /// Parses a patch document, e.g. `[{"op": "remove", "path": "/a"}]`.
+    pub fn parse(source: &str) -> Result<Patch, PatchError> {
+        let root: Node = source.parse().map_err(|message| PatchError {
+            message,
+            index: None,
+            pointer: None,
+            span: None,
+        })?;
+
+        Patch::from_node(&root)
+    }
+
+    pub fn from_node(root: &Node) -> Result<Patch, PatchError> {
+        let items = match root {
+            Node::Array(array) => &array.value,
+            _ => {
+                return Err(PatchError {
+                    message: "a patch must be an array of operations".to_string(),
+                    index: None,
+                    pointer: None,
+                    span: located(root.span()),
+                })
+            }
+        };
+
+        let mut patch = Patch::new(vec![]);
+        for (index, item) in items.iter().enumerate() {
+            patch.operations.push(operation(index, item)?);
+            patch.spans.push(*item.span());
+        }
+
+        Ok(patch)
+    }
+
+    /// Generates a patch that turns `from` into `to`. Object members are
+    /// compared by key and arrays item by item (after skipping their common
+    /// prefix and suffix); moves are not detected.
+    pub fn diff(from: &Node, to: &Node) -> Patch {
+        let mut operations = vec![];
+        diff(from, to, &mut Path::root(), &mut operations);
+        Patch::new(operations)
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// The (synthesized) JSON representation of this patch.
+    pub fn to_node(&self) -> Node {
+        Node::array(self.operations.iter().map(Operation::to_node).collect())
+    }
+
+    /// Applies all operations to `target`. This is atomic: if any operation
+    /// fails, `target` is left untouched and the error names that operation.
+    ///
+    /// Added and copied values get synthetic spans, whereas moved values keep
+    /// theirs.
+    pub fn apply(&self, target: &mut Node) -> Result<(), PatchError> {
+        let mut patched = target.clone();
+
+        for (index, (operation, span)) in self.operations.iter().zip(&self.spans).enumerate() {
+            apply(&mut patched, operation).map_err(|(message, pointer)| PatchError {
+                message,
+                index: Some(index),
+                pointer: Some(pointer),
+                span: located(span),
+            })?;
+        }
+
+        *target = patched;
+        Ok(())
+    }
+}
+
+fn located(span: &Span) -> Option<Span> {
+    Some(*span).filter(|span| !span.is_synthetic())
+}
+
+fn operation(index: usize, node: &Node) -> Result<Operation, PatchError> {
+    let error = |message: String, node: &Node| PatchError {
+        message,
+        index: Some(index),
+        pointer: None,
+        span: located(node.span()),
+    };
+
+    let object = match node {
+        Node::Object(object) => object,
+        _ => return Err(error("an operation must be an object".to_string(), node)),
+    };
+
+    let member = |name: &str| {
+        object
+            .get(name)
+            .ok_or_else(|| error(format!("missing `{}`", name), node))
+    };
+    let pointer = |name: &str| {
+        let value = member(name)?;
+        let pointer = value
+            .as_str()
+            .map_err(|_| error(format!("`{}` must be a string", name), value))?;
+        pointer::parse(pointer).map_err(|message| error(message, value))?;
+        Ok(pointer.to_string())
+    };
+    let value = || member("value").cloned();
+
+    let op = member("op")?;
+    Ok(match op.as_str() {
+        Ok("add") => Operation::Add {
+            path: pointer("path")?,
+            value: value()?,
+        },
+        Ok("remove") => Operation::Remove {
+            path: pointer("path")?,
+        },
+        Ok("replace") => Operation::Replace {
+            path: pointer("path")?,
+            value: value()?,
+        },
+        Ok("move") => Operation::Move {
+            from: pointer("from")?,
+            path: pointer("path")?,
+        },
+        Ok("copy") => Operation::Copy {
+            from: pointer("from")?,
+            path: pointer("path")?,
+        },
+        Ok("test") => Operation::Test {
+            path: pointer("path")?,
+            value: value()?,
+        },
+        _ => {
+            return Err(error(
+                format!(
+                    "`op` must be one of add, remove, replace, move, copy or test, found {}",
+                    op.raw()
+                ),
+                op,
+            ))
+        }
+    })
+}
+
+/// Applies a single operation, failing with a message and the pointer the
+/// failure refers to.
+fn apply(root: &mut Node, operation: &Operation) -> Result<(), (String, String)> {
+    match operation {
+        Operation::Add { path, value } => add(root, path, detached(value)).map_err(at(path)),
+        Operation::Remove { path } => remove(root, path).map(|_| ()).map_err(at(path)),
+        Operation::Replace { path, value } => {
+            replace(root, path, detached(value)).map_err(at(path))
+        }
+        Operation::Move { from, path } => {
+            if path == from {
+                return root.pointer(from).map(|_| ()).map_err(at(from));
+            }
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(at(from)("can not move a value into itself".to_string()));
+            }
+
+            let value = remove(root, from).map_err(at(from))?;
+            add(root, path, value).map_err(at(path))
+        }
+        Operation::Copy { from, path } => {
+            let value = root.pointer(from).map_err(at(from))?;
+            add(root, path, detached(value)).map_err(at(path))
+        }
+        Operation::Test { path, value } => {
+            let actual = root.pointer(path).map_err(at(path))?;
+            if actual.semantic_eq(value) {
+                Ok(())
+            } else {
+                Err(at(path)(format!(
+                    "test failed: expected {}, found {}",
+                    value.raw(),
+                    actual.raw()
+                )))
+            }
+        }
+    }
+}
+
+/// Attributes a failure to `pointer`.
+fn at(pointer: &str) -> impl Fn(String) -> (String, String) + '_ {
+    move |message| (message, pointer.to_string())
+}
+
+fn add(root: &mut Node, path: &str, value: Node) -> Result<(), String> {
+    let tokens = pointer::parse(path)?;
+    if tokens.is_empty() {
+        *root = value;
+        return Ok(());
+    }
+
+    edit_parent(root, &tokens, 0, |parent, token| match parent {
+        Node::Object(object) => {
+            // a replaced member keeps its key, and with it its place
+            match object.get_mut(token) {
+                Some(member) => *member = value,
+                None => {
+                    object.insert(StringNode::synthetic(token), value);
+                }
+            }
+            Ok(())
+        }
+        Node::Array(array) => {
+            let len = array.value.len();
+            let index = match token {
+                "-" => len,
+                _ => array_index(token, len + 1)?,
+            };
+            array.value.insert(index, value);
+            Ok(())
+        }
+        _ => Err("value is neither an array nor an object".to_string()),
+    })
+}
+
+fn remove(root: &mut Node, path: &str) -> Result<Node, String> {
+    let tokens = pointer::parse(path)?;
+    if tokens.is_empty() {
+        return Err("can not remove the whole document".to_string());
+    }
+
+    edit_parent(root, &tokens, 0, |parent, token| match parent {
+        Node::Object(object) => object
+            .remove(token)
+            .ok_or_else(|| format!("no key `{}`", token)),
+        Node::Array(array) => {
+            let index = array_index(token, array.value.len())?;
+            Ok(array.value.remove(index))
+        }
+        _ => Err("value is neither an array nor an object".to_string()),
+    })
+}
+
+fn replace(root: &mut Node, path: &str, value: Node) -> Result<(), String> {
+    let tokens = pointer::parse(path)?;
+    if tokens.is_empty() {
+        *root = value;
+        return Ok(());
+    }
+
+    edit_parent(root, &tokens, 0, |parent, token| {
+        let target = match parent {
+            Node::Object(object) => object
+                .get_mut(token)
+                .ok_or_else(|| format!("no key `{}`", token))?,
+            Node::Array(array) => {
+                let index = array_index(token, array.value.len())?;
+                &mut array.value[index]
+            }
+            _ => return Err("value is neither an array nor an object".to_string()),
+        };
+        *target = value;
+        Ok(())
+    })
+}
+
+/// Calls `edit` with the parent of the node `tokens` refers to and the last
+/// token.
+fn edit_parent<R>(
+    node: &mut Node,
+    tokens: &[String],
+    depth: usize,
+    edit: impl FnOnce(&mut Node, &str) -> Result<R, String>,
+) -> Result<R, String> {
+    let parent = || pointer::format(&tokens[..depth]);
+
+    match &tokens[depth..] {
+        [] => Err("can not edit the whole document".to_string()),
+        [token] => edit(node, token),
+        [token, ..] => {
+            let child = match node {
+                Node::Object(object) => object
+                    .get_mut(token)
+                    .ok_or_else(|| format!("no key `{}` in object at `{}`", token, parent()))?,
+                Node::Array(array) => {
+                    let index = array_index(token, array.value.len())
+                        .map_err(|e| format!("{} at `{}`", e, parent()))?;
+                    &mut array.value[index]
+                }
+                _ => {
+                    return Err(format!(
+                        "value at `{}` is neither an array nor an object",
+                        parent()
+                    ))
+                }
+            };
+            edit_parent(child, tokens, depth + 1, edit)
+        }
+    }
+}
+
+/// Parses an array index that must be less than `bound`.
+fn array_index(token: &str, bound: usize) -> Result<usize, String> {
+    match parse_index(token) {
+        Some(index) if index < bound => Ok(index),
+        Some(_) => Err(format!("index {} out of bounds", token)),
+        None => Err(format!("`{}` is not a valid array index", token)),
+    }
+}
+
+fn diff(from: &Node, to: &Node, path: &mut Path, operations: &mut Vec<Operation>) {
+    if from.semantic_eq(to) {
+        return;
+    }
+
+    match (from, to) {
+        (Node::Object(from), Node::Object(to)) => {
+            for (key, _) in from.entries() {
+                if !to.contains_key(key) {
+                    operations.push(Operation::Remove {
+                        path: path.child(PathSegment::Key(key.clone())).pointer(),
+                    });
+                }
+            }
+
+            for (key, node) in to.entries() {
+                path.push(PathSegment::Key(key.clone()));
+                match from.get(key) {
+                    Some(old) => diff(old, node, path, operations),
+                    None => operations.push(Operation::Add {
+                        path: path.pointer(),
+                        value: node.clone(),
+                    }),
+                }
+                path.pop();
+            }
+        }
+        (Node::Array(from), Node::Array(to)) => {
+            let (from, to) = (&from.value, &to.value);
+            let prefix = from
+                .iter()
+                .zip(to)
+                .take_while(|(a, b)| a.semantic_eq(b))
+                .count();
+            let suffix = from[prefix..]
+                .iter()
+                .rev()
+                .zip(to[prefix..].iter().rev())
+                .take_while(|(a, b)| a.semantic_eq(b))
+                .count();
+            let from = &from[prefix..from.len() - suffix];
+            let to = &to[prefix..to.len() - suffix];
+            let common = from.len().min(to.len());
+
+            for index in 0..common {
+                path.push(PathSegment::Index(prefix + index));
+                diff(&from[index], &to[index], path, operations);
+                path.pop();
+            }
+
+            // surplus items are removed back to front, so that the indices of
+            // the remaining ones stay valid
+            for index in (common..from.len()).rev() {
+                operations.push(Operation::Remove {
+                    path: path.child(PathSegment::Index(prefix + index)).pointer(),
+                });
+            }
+
+            for (index, node) in to.iter().enumerate().skip(common) {
+                operations.push(Operation::Add {
+                    path: path.child(PathSegment::Index(prefix + index)).pointer(),
+                    value: node.clone(),
+                });
+            }
+        }
+        _ => operations.push(Operation::Replace {
+            path: path.pointer(),
+            value: to.clone(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    #[test]
+    fn it_applies_patches() {
+        let mut target = parse_source("{\"a\": {\"b\": [1, 2, 3]}, \"c\": \"x\", \"d~/\": null}");
+        let patch = Patch::parse(
+            "[
+                {\"op\": \"test\", \"path\": \"/c\", \"value\": \"x\"},
+                {\"op\": \"add\", \"path\": \"/a/b/1\", \"value\": 9},
+                {\"op\": \"add\", \"path\": \"/a/b/-\", \"value\": {\"e\": true}},
+                {\"op\": \"remove\", \"path\": \"/a/b/0\"},
+                {\"op\": \"replace\", \"path\": \"/c\", \"value\": [\"y\"]},
+                {\"op\": \"move\", \"from\": \"/d~0~1\", \"path\": \"/a/d\"},
+                {\"op\": \"copy\", \"from\": \"/a/b/3\", \"path\": \"/f\"}
+            ]",
+        )
+        .unwrap();
+
+        patch.apply(&mut target).unwrap();
+        assert!(target.semantic_eq(&parse_source(
+                "{\"a\": {\"b\": [9, 2, 3, {\"e\": true}], \"d\": null}, \"c\": [\"y\"], \"f\": {\"e\": true}}"
+            )));
+        assert_eq!(
+            target.raw(),
+            "{\"a\":{\"b\":[9,2,3,{\"e\":true}],\"d\":null},\"c\":[\"y\"],\"f\":{\"e\":true}}"
+        );
+        assert!(target.pointer("/f").unwrap().span().is_synthetic());
+    }
+
+    #[test]
+    fn it_fails_atomically() {
+        let source = "{\"a\": [1]}";
+        let mut target = parse_source(source);
+        let patch = Patch::parse(
+            "[\n  {\"op\": \"add\", \"path\": \"/b\", \"value\": 1},\n  {\"op\": \"remove\", \"path\": \"/a/5\"}\n]",
+        )
+        .unwrap();
+
+        let error = patch.apply(&mut target).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "index 5 out of bounds in operation 1 at `/a/5`, line 3 col 3"
+        );
+        assert!(target.semantic_eq(&parse_source(source)));
+
+        let patch = Patch::parse("[{\"op\": \"test\", \"path\": \"/a/0\", \"value\": 2}]").unwrap();
+        assert_eq!(patch.apply(&mut target).unwrap_err().index, Some(0));
+
+        let patch =
+            Patch::parse("[{\"op\": \"move\", \"from\": \"/a\", \"path\": \"/a/0\"}]").unwrap();
+        assert!(patch.apply(&mut target).is_err());
+    }
+
+    #[test]
+    fn it_rejects_invalid_patches() {
+        let error = Patch::parse("[{\"op\": \"add\", \"path\": \"/a\"}]").unwrap_err();
+        assert_eq!(error.message, "missing `value`");
+        assert_eq!(error.index, Some(0));
+
+        let error = Patch::parse("[\n  {\"op\": \"drop\", \"path\": \"/a\"}\n]").unwrap_err();
+        assert_eq!(error.span.unwrap().start().line(), 2);
+        assert_eq!(error.span.unwrap().start().column(), 9);
+
+        assert!(Patch::parse("[{\"op\": \"remove\", \"path\": \"a\"}]").is_err());
+        assert!(Patch::parse("{}").is_err());
+    }
+
+    #[test]
+    fn it_generates_patches() {
+        let from =
+            parse_source("{\"a\": [1, 2, 3, 4], \"b\": {\"c\": 1}, \"d\": 1, \"e\": [1, 2]}");
+        let to = parse_source(
+            "{\"a\": [1, 5, 4], \"b\": {\"c\": 2, \"f\": 3}, \"e\": [0, 1, 2], \"g\": null}",
+        );
+
+        let patch = Patch::diff(&from, &to);
+        assert_eq!(
+            patch.to_node().raw(),
+            "[{\"op\":\"remove\",\"path\":\"/d\"},\
+             {\"op\":\"replace\",\"path\":\"/a/1\",\"value\":5},\
+             {\"op\":\"remove\",\"path\":\"/a/2\"},\
+             {\"op\":\"replace\",\"path\":\"/b/c\",\"value\":2},\
+             {\"op\":\"add\",\"path\":\"/b/f\",\"value\":3},\
+             {\"op\":\"add\",\"path\":\"/e/0\",\"value\":0},\
+             {\"op\":\"add\",\"path\":\"/g\",\"value\":null}]"
+        );
+
+        let mut patched = from.clone();
+        patch.apply(&mut patched).unwrap();
+        assert!(patched.semantic_eq(&to));
+        assert!(Patch::diff(&to, &to).operations().is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+use super::node::Node;
+
+/// Splits a JSON Pointer (RFC 6901) into its unescaped reference tokens.
+///
+/// The empty string refers to the whole document and yields no tokens;
+/// every other pointer must start with a `/`.
+pub fn parse(pointer: &str) -> Result<Vec<String>, String> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+
+    if !pointer.starts_with('/') {
+        return Err(format!(
+            "invalid pointer: `{}` must be empty or start with `/`",
+            pointer
+        ));
+    }
+
+    pointer[1..].split('/').map(unescape).collect()
+}
+
+/// Joins reference tokens into a JSON Pointer, escaping each of them.
+pub fn format<I, S>(tokens: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    tokens
+        .into_iter()
+        .map(|token| format!("/{}", escape(token.as_ref())))
+        .collect()
+}
+
+/// Escapes a single reference token: `~` becomes `~0` and `/` becomes `~1`.
+pub fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// Unescapes a single reference token. This is done in a single pass so
+/// that `~01` correctly becomes `~1` rather than `/`.
+pub fn unescape(token: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(token.len());
+    let mut chars = token.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '~' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('0') => result.push('~'),
+            Some('1') => result.push('/'),
+            _ => {
+                return Err(format!(
+                    "invalid pointer: `~` must be followed by `0` or `1` in `{}`",
+                    token
+                ))
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Parses an array index reference token. Per RFC 6901 this is either `0`
+/// or a sequence of digits without a leading zero.
+pub fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || !token.bytes().all(|b| b.is_ascii_digit())
+        || (token.len() > 1 && token.starts_with('0'))
+    {
+        return None;
+    }
+
+    token.parse().ok()
+}
+
+impl Node {
+    /// Looks up the node referenced by a JSON Pointer, e.g. `/key6/2`.
+    /// The span of the found node is available through [`Node::span`].
+    pub fn pointer(&self, pointer: &str) -> Result<&Node, String> {
+        let tokens = parse(pointer)?;
+
+        let mut node = self;
+        for (depth, token) in tokens.iter().enumerate() {
+            let parent = || format(&tokens[..depth]);
+            node = match node {
+                Node::Object(object) => object.get(token).ok_or_else(|| {
+                    format!(
+                        "pointer `{}` not found: no key `{}` in object at `{}`",
+                        pointer,
+                        token,
+                        parent()
+                    )
+                })?,
+                Node::Array(array) => {
+                    let index = if token == "-" {
+                        // `-` refers to the (nonexistent) element after the last one
+                        array.value.len()
+                    } else {
+                        parse_index(token).ok_or_else(|| {
+                            format!(
+                                "pointer `{}` not found: `{}` is not a valid array index at `{}`",
+                                pointer,
+                                token,
+                                parent()
+                            )
+                        })?
+                    };
+
+                    array.value.get(index).ok_or_else(|| {
+                        format!(
+                            "pointer `{}` not found: index {} out of bounds for array of length {} at `{}`",
+                            pointer,
+                            token,
+                            array.value.len(),
+                            parent()
+                        )
+                    })?
+                }
+                _ => {
+                    return Err(format!(
+                        "pointer `{}` not found: value at `{}` is neither an array nor an object",
+                        pointer,
+                        parent()
+                    ))
+                }
+            };
+        }
+
+        Ok(node)
+    }
+
+    /// Computes the JSON Pointer of `target` within this tree. The target is
+    /// matched by identity, so it must be a reference into this very tree.
+    pub fn pointer_of(&self, target: &Node) -> Option<String> {
+        let mut tokens = vec![];
+        if self.find_path(&mut tokens, &|node| std::ptr::eq(node, target)) {
+            Some(format(&tokens))
+        } else {
+            None
+        }
+    }
+
+    /// Computes the JSON Pointer of the innermost node whose span contains the
+    /// given source offset (cursor), or `None` if it is outside of this tree.
+    /// An offset on an object key resolves to the value of that key.
+    pub fn pointer_at(&self, cursor: usize) -> Option<String> {
+        self.node_at(cursor).map(|at| at.pointer())
+    }
+
+    /// Depth-first search for the first node matching `predicate`, pushing
+    /// the reference tokens leading to it onto `tokens`.
+    fn find_path(&self, tokens: &mut Vec<String>, predicate: &dyn Fn(&Node) -> bool) -> bool {
+        if predicate(self) {
+            return true;
+        }
+
+        match self {
+            Node::Array(array) => {
+                for (index, child) in array.value.iter().enumerate() {
+                    tokens.push(index.to_string());
+                    if child.find_path(tokens, predicate) {
+                        return true;
+                    }
+                    tokens.pop();
+                }
+            }
+            Node::Object(object) => {
+                for (key, child) in object.entries() {
+                    tokens.push(key.clone());
+                    if child.find_path(tokens, predicate) {
+                        return true;
+                    }
+                    tokens.pop();
+                }
+            }
+            _ => {}
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    #[test]
+    fn it_escapes_and_unescapes_tokens() {
+        assert_eq!(parse("/a~1b/c~0d/~01").unwrap(), vec!["a/b", "c~d", "~1"]);
+        assert_eq!(format(["a/b", "c~d"]), "/a~1b/c~0d");
+        assert_eq!(parse("").unwrap(), Vec::<String>::new());
+        assert!(parse("a").is_err());
+        assert!(parse("/a~2").is_err());
+    }
+
+    #[test]
+    fn it_looks_up_pointers() {
+        let root = parse_source("{\"key6\": [\"a\", \"b\", \"c\"], \"a/b\": null}");
+
+        let node = root.pointer("/key6/2").unwrap();
+        assert_eq!(node.span().start().cursor(), 20);
+        assert!(root.pointer("/a~1b").unwrap().is_null());
+        assert!(std::ptr::eq(root.pointer("").unwrap(), &root));
+
+        assert!(root.pointer("/key6/3").is_err());
+        assert!(root.pointer("/key6/01").is_err());
+        assert!(root.pointer("/key6/-").is_err());
+        assert!(root.pointer("/missing").is_err());
+    }
+
+    #[test]
+    fn it_computes_pointers_of_nodes() {
+        let root = parse_source("{\"a/b\": [null, [true]]}");
+
+        let node = root.pointer("/a~1b/1/0").unwrap();
+        assert_eq!(root.pointer_of(node).unwrap(), "/a~1b/1/0");
+        assert_eq!(
+            root.pointer_at(node.span().start().cursor()).unwrap(),
+            "/a~1b/1/0"
+        );
+        assert_eq!(root.pointer_at(0).unwrap(), "");
+        assert_eq!(root.pointer_at(1000), None);
+    }
+}
@@ -0,0 +1,681 @@
+use super::limits::Limits;
+use super::node::{
+    ArrayNode, BooleanNode, Node, NullNode, NumberNode, ObjectNode, Position, Span, StringNode,
+};
+use super::tokenizer::{Token, TokenKind, Tokenizer};
+use super::{
+    boolean_literal, depth_error, located, null_literal, number_literal, string_literal,
+    DEFAULT_MAX_DEPTH,
+};
+
+/// A parsing event, see [`PullParser`].
+#[derive(Debug, PartialEq, Clone)]
+pub enum JsonEvent {
+    /// Carries the span of the opening brace.
+    StartObject(Span),
+    Key(StringNode),
+    /// Carries the span of the whole object.
+    EndObject(Span),
+    /// Carries the span of the opening bracket.
+    StartArray(Span),
+    /// Carries the span of the whole array.
+    EndArray(Span),
+    Null(NullNode),
+    Boolean(BooleanNode),
+    Number(NumberNode),
+    String(StringNode),
+}
+
+impl JsonEvent {
+    pub fn span(&self) -> Span {
+        match self {
+            JsonEvent::StartObject(span)
+            | JsonEvent::EndObject(span)
+            | JsonEvent::StartArray(span)
+            | JsonEvent::EndArray(span) => *span,
+            JsonEvent::Key(node) | JsonEvent::String(node) => node.span,
+            JsonEvent::Null(node) => node.span,
+            JsonEvent::Boolean(node) => node.span,
+            JsonEvent::Number(node) => node.span,
+        }
+    }
+}
+
+/// A parsing event with the token it was read from, before any literal is
+/// converted into a node.
+pub(crate) enum RawEvent<'source> {
+    StartObject(Span),
+    Key(Token<'source>),
+    EndObject(Span),
+    StartArray(Span),
+    EndArray(Span),
+    /// A null, boolean, number or string literal.
+    Scalar(Token<'source>),
+}
+
+impl RawEvent<'_> {
+    fn describe(&self) -> &'static str {
+        match self {
+            RawEvent::Key(_) => "a key",
+            RawEvent::EndObject(_) | RawEvent::EndArray(_) => "the end of a container",
+            _ => "a value",
+        }
+    }
+}
+
+/// Why reading an event stopped.
+enum Halt {
+    Error(String),
+    /// More of a partial source is needed, see [`PullParser::resume`].
+    Starved,
+}
+
+impl From<String> for Halt {
+    fn from(message: String) -> Halt {
+        Halt::Error(message)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum State {
+    /// Expecting a key (in objects), an item (in arrays) or the end.
+    Head,
+    /// Expecting the colon after a key that was just read.
+    Colon,
+    /// Expecting the value of a key.
+    Value,
+    /// Expecting a comma or the end.
+    After,
+}
+
+struct Frame {
+    object: bool,
+    start: Position,
+    state: State,
+    /// The number of members or items so far.
+    len: usize,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Root {
+    Start,
+    Done,
+    Finished,
+}
+
+/// An iterator over the parsing events of a document, accepting the same
+/// documents as [`Parser`](super::Parser). Tokens are read lazily, so memory
+/// use only grows with the nesting depth of the document.
+///
+/// After an error, the iterator ends.
+pub struct PullParser<'source> {
+    tokenizer: Tokenizer<'source>,
+    // the next token, once it was needed
+    current: Option<Token<'source>>,
+    stack: Vec<Frame>,
+    root: Root,
+    // an event that was read ahead of time
+    peeked: Option<RawEvent<'source>>,
+    failed: bool,
+    // whether the last event could not be read for lack of input
+    starved: bool,
+    max_depth: usize,
+    limits: Limits,
+}
+
+impl<'source> PullParser<'source> {
+    pub(crate) fn new(source: &'source str, comments: bool) -> PullParser<'source> {
+        let mut tokenizer = Tokenizer::new(comments);
+        tokenizer.start(source);
+
+        PullParser {
+            tokenizer,
+            current: None,
+            stack: vec![],
+            root: Root::Start,
+            peeked: None,
+            failed: false,
+            starved: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            limits: Limits::default(),
+        }
+    }
+
+    pub fn new_without_comments(source: &'source str) -> PullParser<'source> {
+        PullParser::new(source, false)
+    }
+
+    pub fn new_with_comments(source: &'source str) -> PullParser<'source> {
+        PullParser::new(source, true)
+    }
+
+    /// Limits how deeply arrays and objects may be nested, see
+    /// [`Parser::max_depth`](super::Parser::max_depth).
+    pub fn max_depth(mut self, depth: usize) -> PullParser<'source> {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Caps the resources parsing may take, see [`Limits`].
+    pub fn limits(mut self, limits: Limits) -> PullParser<'source> {
+        self.tokenizer.set_limits(limits);
+        self.limits = limits;
+        self
+    }
+
+    /// The number of containers the parser is currently in.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Detaches the parser from its source, to [`PullParser::resume`] it with
+    /// more input once it is starved.
+    pub(crate) fn suspend(self) -> PullParser<'static> {
+        PullParser {
+            tokenizer: self.tokenizer.detach(),
+            current: None,
+            stack: self.stack,
+            root: self.root,
+            peeked: None,
+            failed: self.failed,
+            starved: false,
+            max_depth: self.max_depth,
+            limits: self.limits,
+        }
+    }
+
+    /// The byte offset in the input up to which the source has been read.
+    pub(crate) fn cursor(&self) -> usize {
+        self.tokenizer.position().cursor()
+    }
+
+    /// Whether the last call to [`PullParser::next_raw`] ended because more
+    /// of a partial source is needed, rather than at the end of the document.
+    pub(crate) fn is_starved(&self) -> bool {
+        self.starved
+    }
+
+    /// The next event, or `None` at the end of the document.
+    pub fn next_event(&mut self) -> Result<Option<JsonEvent>, String> {
+        let event = match self.next_raw()? {
+            Some(event) => event,
+            None => return Ok(None),
+        };
+
+        Ok(Some(match event {
+            RawEvent::StartObject(span) => JsonEvent::StartObject(span),
+            RawEvent::Key(token) => JsonEvent::Key(string_literal(&token)),
+            RawEvent::EndObject(span) => JsonEvent::EndObject(span),
+            RawEvent::StartArray(span) => JsonEvent::StartArray(span),
+            RawEvent::EndArray(span) => JsonEvent::EndArray(span),
+            RawEvent::Scalar(token) => match token.kind() {
+                TokenKind::NullLiteral => JsonEvent::Null(null_literal(&token)),
+                TokenKind::BooleanLiteral => JsonEvent::Boolean(boolean_literal(&token)),
+                TokenKind::NumberLiteral => JsonEvent::Number(number_literal(&token)?),
+                _ => JsonEvent::String(string_literal(&token)),
+            },
+        }))
+    }
+
+    /// The next event with its token, before it is converted into a node.
+    pub(crate) fn next_raw(&mut self) -> Result<Option<RawEvent<'source>>, String> {
+        if let Some(event) = self.peeked.take() {
+            return Ok(Some(event));
+        }
+
+        if self.failed {
+            return Ok(None);
+        }
+
+        self.starved = false;
+        match self.read_event() {
+            Ok(event) => Ok(event),
+            Err(Halt::Starved) => {
+                self.starved = true;
+                Ok(None)
+            }
+            Err(Halt::Error(message)) => {
+                self.failed = true;
+                Err(message)
+            }
+        }
+    }
+
+    /// Puts back an event read with [`PullParser::next_raw`].
+    pub(crate) fn push_back(&mut self, event: RawEvent<'source>) {
+        self.peeked = Some(event);
+    }
+
+    /// Skips the value the next event would start, e.g. the value of a key
+    /// after a [`JsonEvent::Key`], without reporting the events within it.
+    pub fn skip_value(&mut self) -> Result<(), String> {
+        match self.next_raw()? {
+            Some(RawEvent::StartObject(_) | RawEvent::StartArray(_)) => {
+                self.skip_container()?;
+                Ok(())
+            }
+            Some(RawEvent::Scalar(_)) => Ok(()),
+            Some(event) => {
+                let message = format!("expected a value to skip, found {}", event.describe());
+                self.peeked = Some(event);
+                Err(message)
+            }
+            None => Err("expected a value to skip, found the end of input".to_string()),
+        }
+    }
+
+    /// Builds the value the next event would start as a [`Node`], exactly as
+    /// [`Parser`](super::Parser) would have.
+    pub fn read_node(&mut self) -> Result<Node, String> {
+        let event = self
+            .next_raw()?
+            .ok_or_else(|| "expected a value to read, found the end of input".to_string())?;
+
+        Ok(match event {
+            RawEvent::Scalar(token) => scalar_node(&token)?,
+            RawEvent::StartArray(_) => {
+                let mut items = vec![];
+                let span = loop {
+                    match self.next_raw()? {
+                        Some(RawEvent::EndArray(span)) => break span,
+                        event => {
+                            self.peeked = event;
+                            items.push(self.read_node()?);
+                        }
+                    }
+                };
+
+                Node::Array(ArrayNode { span, value: items })
+            }
+            RawEvent::StartObject(_) => {
+                let mut object = ObjectNode::empty(Span::empty());
+                object.span = loop {
+                    match self.next_raw()? {
+                        Some(RawEvent::EndObject(span)) => break span,
+                        Some(RawEvent::Key(token)) => {
+                            let key = string_literal(&token);
+                            object.insert(key, self.read_node()?);
+                        }
+                        _ => return Err("expected a key or the end of an object".to_string()),
+                    }
+                };
+
+                Node::Object(object)
+            }
+            event => {
+                let message = format!("expected a value to read, found {}", event.describe());
+                self.peeked = Some(event);
+                return Err(message);
+            }
+        })
+    }
+
+    /// Skips the rest of the container that was just started, returning its
+    /// span.
+    pub(crate) fn skip_container(&mut self) -> Result<Span, String> {
+        let depth = self.stack.len();
+
+        loop {
+            match self.next_raw()? {
+                Some(RawEvent::EndObject(span) | RawEvent::EndArray(span))
+                    if self.stack.len() < depth =>
+                {
+                    return Ok(span)
+                }
+                Some(_) => continue,
+                None => return Err("unexpected end of input while skipping".to_string()),
+            }
+        }
+    }
+
+    /// Reads the next event. Tokens are only read when they are needed and
+    /// the state only changes once they are there, so a starved parser can be
+    /// resumed where it stopped.
+    fn read_event(&mut self) -> Result<Option<RawEvent<'source>>, Halt> {
+        loop {
+            let (object, start, state) = match self.stack.last() {
+                Some(frame) => (frame.object, frame.start, frame.state),
+                None => {
+                    return match self.root {
+                        Root::Start => {
+                            let event = self.value()?;
+                            self.root = Root::Done;
+                            Ok(Some(event))
+                        }
+                        Root::Done => {
+                            self.consume(TokenKind::Eoi)?;
+                            self.root = Root::Finished;
+                            Ok(None)
+                        }
+                        Root::Finished => Ok(None),
+                    };
+                }
+            };
+
+            let close = if object {
+                TokenKind::ClosedCurlyBrace
+            } else {
+                TokenKind::ClosedSquareBracket
+            };
+
+            match state {
+                State::Head
+                    if self.current_kind()? == close || self.current_kind()? == TokenKind::Eoi =>
+                {
+                    let end = self.consume(close)?.span().end();
+                    self.stack.pop();
+
+                    let span = Span::new(start, end);
+                    return Ok(Some(if object {
+                        RawEvent::EndObject(span)
+                    } else {
+                        RawEvent::EndArray(span)
+                    }));
+                }
+                State::Head if object => {
+                    let key = self.consume(TokenKind::StringLiteral)?;
+                    self.count_entry(key.span())?;
+                    self.set_state(State::Colon);
+                    return Ok(Some(RawEvent::Key(key)));
+                }
+                State::Colon => {
+                    self.consume(TokenKind::Colon)?;
+                    self.set_state(State::Value);
+                }
+                State::Head | State::Value => {
+                    // read the token first, changing nothing if starved
+                    self.current_kind()?;
+                    if !object {
+                        let span = self.current.as_ref().map(|token| *token.span());
+                        if let Some(span) = span {
+                            self.count_entry(&span)?;
+                        }
+                    }
+                    self.set_state(State::After);
+                    return self.value().map(Some);
+                }
+                State::After => {
+                    if self.current_kind()? != close {
+                        self.consume(TokenKind::Comma)?;
+                    }
+                    self.set_state(State::Head);
+                }
+            }
+        }
+    }
+
+    /// Reads a scalar or the start of a container.
+    fn value(&mut self) -> Result<RawEvent<'source>, Halt> {
+        Ok(match self.current_kind()? {
+            kind @ (TokenKind::NullLiteral
+            | TokenKind::BooleanLiteral
+            | TokenKind::NumberLiteral
+            | TokenKind::StringLiteral) => RawEvent::Scalar(self.consume(kind)?),
+            kind @ (TokenKind::OpenCurlyBrace | TokenKind::OpenSquareBracket) => {
+                let object = kind == TokenKind::OpenCurlyBrace;
+                let span = *self.consume(kind)?.span();
+                if self.stack.len() >= self.max_depth {
+                    return Err(depth_error(self.max_depth, &span).into());
+                }
+                self.stack.push(Frame {
+                    object,
+                    start: span.start(),
+                    state: State::Head,
+                    len: 0,
+                });
+
+                if object {
+                    RawEvent::StartObject(span)
+                } else {
+                    RawEvent::StartArray(span)
+                }
+            }
+            kind => {
+                return Err(
+                    format!("unexpected token: found `{:?}`, expected a value", kind).into(),
+                )
+            }
+        })
+    }
+
+    /// Counts a member or item of the innermost container, which starts at
+    /// `span`.
+    fn count_entry(&mut self, span: &Span) -> Result<(), String> {
+        match self.stack.last_mut() {
+            Some(frame) => {
+                frame.len += 1;
+                self.limits.check_entries(frame.object, frame.len, span)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn set_state(&mut self, state: State) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.state = state;
+        }
+    }
+
+    /// The kind of the next token, reading it if it is not there yet.
+    fn current_kind(&mut self) -> Result<TokenKind, Halt> {
+        if self.current.is_none() {
+            self.current = Some(self.tokenizer.next_token()?.ok_or(Halt::Starved)?);
+        }
+
+        Ok(self
+            .current
+            .as_ref()
+            .map_or(TokenKind::Eoi, |token| *token.kind()))
+    }
+
+    /// Expects the next token to be of the given kind and advances past it.
+    fn consume(&mut self, kind: TokenKind) -> Result<Token<'source>, Halt> {
+        let found = self.current_kind()?;
+        if found != kind {
+            return Err(format!(
+                "unexpected token: found `{:?}`, expected `{:?}`",
+                found, &kind
+            )
+            .into());
+        }
+
+        self.current
+            .take()
+            .ok_or_else(|| "unexpected end of input".to_string().into())
+    }
+}
+
+impl PullParser<'static> {
+    /// Continues a suspended parser with `source`, the rest of the input from
+    /// [`PullParser::cursor`] on. Unless `partial` is false, more input may
+    /// follow it: the parser is starved rather than failing when it runs out.
+    pub(crate) fn resume(self, source: &str, partial: bool) -> PullParser<'_> {
+        let mut parser: PullParser = self;
+        parser.tokenizer.feed(source, partial);
+        parser
+    }
+}
+
+impl Iterator for PullParser<'_> {
+    type Item = Result<JsonEvent, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+/// The node of a null, boolean, number or string literal.
+fn scalar_node(token: &Token) -> Result<Node, String> {
+    Ok(match token.kind() {
+        TokenKind::NullLiteral => Node::Null(null_literal(token)),
+        TokenKind::BooleanLiteral => Node::Boolean(boolean_literal(token)),
+        TokenKind::NumberLiteral => Node::Number(number_literal(token)?),
+        _ => Node::String(string_literal(token)),
+    })
+}
+
+/// A container being built by a [`NodeBuilder`].
+enum Partial {
+    Array(ArrayNode),
+    /// With the key of the member whose value is being read.
+    Object(ObjectNode, Option<StringNode>),
+}
+
+/// Builds a [`Node`] from the events of a [`PullParser`] as
+/// [`Parser`](super::Parser) would have, but without recursing.
+pub(crate) struct NodeBuilder {
+    deny_duplicate_keys: bool,
+    stack: Vec<Partial>,
+    root: Option<Node>,
+}
+
+impl NodeBuilder {
+    pub(crate) fn new(deny_duplicate_keys: bool) -> NodeBuilder {
+        NodeBuilder {
+            deny_duplicate_keys,
+            stack: vec![],
+            root: None,
+        }
+    }
+
+    /// The root node, once it is complete.
+    pub(crate) fn finish(self) -> Option<Node> {
+        self.root
+    }
+
+    /// Adds the next event.
+    pub(crate) fn push(&mut self, event: RawEvent) -> Result<(), String> {
+        let node = match event {
+            RawEvent::StartArray(span) => {
+                self.stack.push(Partial::Array(ArrayNode {
+                    span,
+                    value: vec![],
+                }));
+                return Ok(());
+            }
+            RawEvent::StartObject(span) => {
+                self.stack
+                    .push(Partial::Object(ObjectNode::empty(span), None));
+                return Ok(());
+            }
+            RawEvent::Key(token) => {
+                let key = string_literal(&token);
+                if let Some(Partial::Object(object, pending)) = self.stack.last_mut() {
+                    if self.deny_duplicate_keys && object.contains_key(&key.value) {
+                        return Err(located(format!("duplicate key `{}`", key.value), &key.span));
+                    }
+                    *pending = Some(key);
+                }
+                return Ok(());
+            }
+            RawEvent::EndArray(span) | RawEvent::EndObject(span) => match self.stack.pop() {
+                Some(Partial::Array(mut array)) => {
+                    array.span = span;
+                    Node::Array(array)
+                }
+                Some(Partial::Object(mut object, _)) => {
+                    object.span = span;
+                    Node::Object(object)
+                }
+                None => return Err("unexpected end of a container".to_string()),
+            },
+            RawEvent::Scalar(token) => scalar_node(&token)?,
+        };
+
+        match self.stack.last_mut() {
+            Some(Partial::Array(array)) => array.value.push(node),
+            Some(Partial::Object(object, key)) => {
+                if let Some(key) = key.take() {
+                    // unless duplicates are denied, the last member with a given key wins
+                    object.insert(key, node);
+                }
+            }
+            None => self.root = Some(node),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str =
+        "{\"skip\": {\"a\": [1, {\"b\": null}]}, \"find\": [true, \"x\", 2.5], \"rest\": {}}";
+
+    #[test]
+    fn it_iterates_events() {
+        let events: Vec<_> = PullParser::new_without_comments("{\"a\": [1, null], \"b\": {}}")
+            .map(|event| match event.unwrap() {
+                JsonEvent::StartObject(_) => "{".to_string(),
+                JsonEvent::Key(key) => format!("{}:", key.value),
+                JsonEvent::EndObject(span) => format!("}}{}", span.end().cursor()),
+                JsonEvent::StartArray(_) => "[".to_string(),
+                JsonEvent::EndArray(_) => "]".to_string(),
+                JsonEvent::Number(node) => node.raw,
+                event => format!("{:?}", event.span().start().cursor()),
+            })
+            .collect();
+
+        assert_eq!(
+            events,
+            vec!["{", "a:", "[", "1", "10", "]", "b:", "{", "}24", "}25"]
+        );
+    }
+
+    #[test]
+    fn it_skips_and_reads_values() {
+        let mut parser = PullParser::new_without_comments(SOURCE);
+        assert!(matches!(
+            parser.next_event(),
+            Ok(Some(JsonEvent::StartObject(_)))
+        ));
+
+        let mut found = None;
+        while let Some(event) = parser.next_event().unwrap() {
+            match event {
+                JsonEvent::Key(key) if key.value == "find" => {
+                    found = Some(parser.read_node().unwrap())
+                }
+                JsonEvent::Key(_) => parser.skip_value().unwrap(),
+                _ => {}
+            }
+        }
+
+        let expected = SOURCE.parse::<Node>().unwrap();
+        assert_eq!(found.as_ref(), Some(expected.pointer("/find").unwrap()));
+        assert_eq!(parser.depth(), 0);
+    }
+
+    #[test]
+    fn it_reports_the_same_errors_as_the_parser() {
+        for source in [
+            "{\"a\" 1}",
+            "[1 2]",
+            "[1,",
+            "",
+            "1 2",
+            "{1: 2}",
+            "{\"a\": }",
+            "[1, }",
+        ] {
+            let expected = source.parse::<Node>().unwrap_err();
+            let actual = PullParser::new_without_comments(source)
+                .find_map(Result::err)
+                .unwrap();
+            assert_eq!(actual, expected, "parsing {:?}", source);
+        }
+
+        let mut parser = PullParser::new_without_comments("[1, 2]");
+        parser.next_event().unwrap();
+        parser.read_node().unwrap();
+        parser.read_node().unwrap();
+        assert!(parser.skip_value().is_err());
+        assert!(matches!(
+            parser.next_event(),
+            Ok(Some(JsonEvent::EndArray(_)))
+        ));
+        assert_eq!(parser.next_event(), Ok(None));
+    }
+}
@@ -0,0 +1,278 @@
+use super::limits::Limits;
+use super::node::{BooleanNode, NullNode, NumberNode, Span, StringNode};
+use super::pull::{JsonEvent, PullParser};
+use super::visit::Control;
+use super::DEFAULT_MAX_DEPTH;
+
+/// Callbacks for the events of [`SaxParser::parse`], in document order.
+/// Every callback defaults to doing nothing and continuing.
+///
+/// Returning [`Control::Stop`] aborts parsing. Returning
+/// [`Control::SkipChildren`] from `start_object` or `start_array` parses the
+/// container without reporting any events for its contents; its `end_*`
+/// callback is still called.
+#[allow(unused_variables)]
+pub trait Handler {
+    /// `span` is the span of the opening brace.
+    fn start_object(&mut self, span: Span) -> Control {
+        Control::Continue
+    }
+
+    /// Called for every object key, before the events of its value.
+    fn key(&mut self, key: &StringNode) -> Control {
+        Control::Continue
+    }
+
+    /// `span` is the span of the whole object.
+    fn end_object(&mut self, span: Span) -> Control {
+        Control::Continue
+    }
+
+    /// `span` is the span of the opening bracket.
+    fn start_array(&mut self, span: Span) -> Control {
+        Control::Continue
+    }
+
+    /// `span` is the span of the whole array.
+    fn end_array(&mut self, span: Span) -> Control {
+        Control::Continue
+    }
+
+    fn null(&mut self, node: &NullNode) -> Control {
+        Control::Continue
+    }
+
+    fn boolean(&mut self, node: &BooleanNode) -> Control {
+        Control::Continue
+    }
+
+    fn number(&mut self, node: &NumberNode) -> Control {
+        Control::Continue
+    }
+
+    fn string(&mut self, node: &StringNode) -> Control {
+        Control::Continue
+    }
+}
+
+/// An event-driven parser accepting the same documents as
+/// [`Parser`](super::Parser), without building a tree. Built on
+/// [`PullParser`], so memory use only grows with the nesting depth of the
+/// document.
+pub struct SaxParser {
+    comments: bool,
+    max_depth: usize,
+    limits: Limits,
+}
+
+impl SaxParser {
+    pub fn new_without_comments() -> SaxParser {
+        SaxParser {
+            comments: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            limits: Limits::default(),
+        }
+    }
+
+    pub fn new_with_comments() -> SaxParser {
+        SaxParser {
+            comments: true,
+            max_depth: DEFAULT_MAX_DEPTH,
+            limits: Limits::default(),
+        }
+    }
+
+    /// Limits how deeply arrays and objects may be nested, see
+    /// [`Parser::max_depth`](super::Parser::max_depth).
+    pub fn max_depth(mut self, depth: usize) -> SaxParser {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Caps the resources parsing may take, see [`Limits`].
+    pub fn limits(mut self, limits: Limits) -> SaxParser {
+        self.limits = limits;
+        self
+    }
+
+    /// Parses `source`, calling the callbacks of `handler` along the way.
+    /// Returns `false` if parsing was stopped early by a callback returning
+    /// [`Control::Stop`].
+    pub fn parse<H: Handler>(&self, source: &str, handler: &mut H) -> Result<bool, String> {
+        let mut events = PullParser::new(source, self.comments)
+            .max_depth(self.max_depth)
+            .limits(self.limits);
+
+        while let Some(event) = events.next_event()? {
+            let control = match &event {
+                JsonEvent::StartObject(span) => handler.start_object(*span),
+                JsonEvent::Key(key) => handler.key(key),
+                JsonEvent::EndObject(span) => handler.end_object(*span),
+                JsonEvent::StartArray(span) => handler.start_array(*span),
+                JsonEvent::EndArray(span) => handler.end_array(*span),
+                JsonEvent::Null(node) => handler.null(node),
+                JsonEvent::Boolean(node) => handler.boolean(node),
+                JsonEvent::Number(node) => handler.number(node),
+                JsonEvent::String(node) => handler.string(node),
+            };
+
+            match (control, event) {
+                (Control::Stop, _) => return Ok(false),
+                (Control::SkipChildren, JsonEvent::StartObject(_)) => {
+                    let span = events.skip_container()?;
+                    if handler.end_object(span) == Control::Stop {
+                        return Ok(false);
+                    }
+                }
+                (Control::SkipChildren, JsonEvent::StartArray(_)) => {
+                    let span = events.skip_container()?;
+                    if handler.end_array(span) == Control::Stop {
+                        return Ok(false);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records events as strings.
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+        stop_at: Option<&'static str>,
+        skip: bool,
+    }
+
+    impl Recorder {
+        fn record(&mut self, event: String) -> Control {
+            let stop = self.stop_at.is_some_and(|stop_at| event == stop_at);
+            self.events.push(event);
+            if stop {
+                Control::Stop
+            } else {
+                Control::Continue
+            }
+        }
+    }
+
+    impl Handler for Recorder {
+        fn start_object(&mut self, span: Span) -> Control {
+            self.record(format!("{{@{}", span.start().cursor()));
+            if self.skip && span.start().cursor() > 0 {
+                Control::SkipChildren
+            } else {
+                Control::Continue
+            }
+        }
+
+        fn key(&mut self, key: &StringNode) -> Control {
+            self.record(format!("key {}", key.value))
+        }
+
+        fn end_object(&mut self, span: Span) -> Control {
+            self.record(format!("}}@{}", span.end().cursor()))
+        }
+
+        fn start_array(&mut self, _span: Span) -> Control {
+            self.record("[".to_string())
+        }
+
+        fn end_array(&mut self, _span: Span) -> Control {
+            self.record("]".to_string())
+        }
+
+        fn null(&mut self, _node: &NullNode) -> Control {
+            self.record("null".to_string())
+        }
+
+        fn boolean(&mut self, node: &BooleanNode) -> Control {
+            self.record(node.raw.clone())
+        }
+
+        fn number(&mut self, node: &NumberNode) -> Control {
+            self.record(format!("{}@{}", node.raw, node.span.start().cursor()))
+        }
+
+        fn string(&mut self, node: &StringNode) -> Control {
+            self.record(node.raw.clone())
+        }
+    }
+
+    const SOURCE: &str = "{\"a\": [1, true, null], \"b\": {\"c\": \"d\"}, \"e\": 2.5}";
+
+    #[test]
+    fn it_emits_events() {
+        let mut recorder = Recorder::default();
+        assert_eq!(
+            SaxParser::new_without_comments().parse(SOURCE, &mut recorder),
+            Ok(true)
+        );
+        assert_eq!(
+            recorder.events,
+            vec![
+                "{@0", "key a", "[", "1@7", "true", "null", "]", "key b", "{@28", "key c", "\"d\"",
+                "}@38", "key e", "2.5@45", "}@49"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_skips_and_stops() {
+        let mut recorder = Recorder {
+            skip: true,
+            ..Recorder::default()
+        };
+        SaxParser::new_without_comments()
+            .parse(SOURCE, &mut recorder)
+            .unwrap();
+        assert!(recorder.events.contains(&"}@38".to_string()));
+        assert!(!recorder.events.contains(&"key c".to_string()));
+
+        let mut recorder = Recorder {
+            stop_at: Some("key b"),
+            ..Recorder::default()
+        };
+        assert_eq!(
+            SaxParser::new_without_comments().parse(SOURCE, &mut recorder),
+            Ok(false)
+        );
+        assert_eq!(recorder.events.last().unwrap(), "key b");
+    }
+
+    #[test]
+    fn it_reports_the_same_errors_as_the_parser() {
+        for source in [
+            "{\"a\" 1}",
+            "[1 2]",
+            "[1,",
+            "",
+            "1 2",
+            "// c\n1",
+            "{\"a\": }",
+            "[1, }",
+        ] {
+            let expected = source.parse::<crate::parser::node::Node>().unwrap_err();
+            let actual = SaxParser::new_without_comments()
+                .parse(source, &mut Recorder::default())
+                .unwrap_err();
+            assert_eq!(actual, expected, "parsing {:?}", source);
+        }
+
+        let deep = "[".repeat(100_000);
+        assert_eq!(
+            SaxParser::new_without_comments().parse(&deep, &mut Recorder::default()),
+            Err("nesting deeper than 128 levels at line 1 col 129".to_string())
+        );
+
+        assert!(SaxParser::new_with_comments()
+            .parse("// c\n1", &mut Recorder::default())
+            .unwrap());
+    }
+}
@@ -0,0 +1,784 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use regex::Regex;
+
+use super::node::{Node, NumberNodeValue, Span};
+use super::pointer;
+use super::visit::{Path, PathSegment};
+use super::Parser;
+
+/// The errors of validating an instance node against a schema node, by the
+/// addresses of both. Every schema node has a single schema path and every
+/// instance node a single instance path, so these are the same wherever the
+/// pair is reached from.
+type Memo = HashMap<(*const Node, *const Node), Vec<ValidationError>>;
+
+const TYPE_NAMES: [&str; 7] = [
+    "null", "boolean", "object", "array", "number", "string", "integer",
+];
+
+/// An error in the schema document itself.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SchemaError {
+    pub message: String,
+    /// The JSON Pointer of the offending part of the schema.
+    pub schema_path: String,
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid schema: {} at `{}`",
+            self.message, self.schema_path
+        )?;
+        if let Some(span) = self.span {
+            write!(
+                f,
+                ", line {} col {}",
+                span.start().line(),
+                span.start().column() + 1
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A violation of a schema by an instance.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ValidationError {
+    pub message: String,
+    pub instance_path: Path,
+    /// The span of the offending instance node, unless it was synthesized.
+    pub instance_span: Option<Span>,
+    /// The JSON Pointer of the violated keyword within the schema document.
+    pub schema_path: String,
+    pub schema_span: Option<Span>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.instance_path.depth() == 0 {
+            write!(f, "{} at the root", self.message)?;
+        } else {
+            write!(f, "{} at {}", self.message, self.instance_path)?;
+        }
+
+        if let Some(span) = self.instance_span {
+            write!(
+                f,
+                ", line {} col {}",
+                span.start().line(),
+                span.start().column() + 1
+            )?;
+        }
+
+        write!(f, " (schema `{}`)", self.schema_path)
+    }
+}
+
+/// A JSON Schema (draft 2020-12) supporting the `type`, `enum`, `const`,
+/// `properties`, `additionalProperties`, `required`, `pattern`, `minimum`,
+/// `maximum`, `exclusiveMinimum`, `exclusiveMaximum`, `multipleOf`, `items`,
+/// `allOf`, `anyOf`, `oneOf`, `$defs` and local `$ref` keywords. Unknown
+/// keywords are ignored.
+#[derive(Debug)]
+pub struct Schema {
+    root: Node,
+    patterns: HashMap<String, Regex>,
+}
+
+impl Schema {
+    /// Parses (with comments allowed) and checks a schema document.
+    pub fn parse(source: &str) -> Result<Schema, SchemaError> {
+        let root = Parser::new_with_comments()
+            .parse_str(source)
+            .map_err(|message| SchemaError {
+                message,
+                schema_path: String::new(),
+                span: None,
+            })?;
+
+        Schema::new(root)
+    }
+
+    /// Checks that `root` is a well-formed schema. Errors point at the
+    /// offending part of the schema document.
+    pub fn new(root: Node) -> Result<Schema, SchemaError> {
+        let mut schema = Schema {
+            root,
+            patterns: HashMap::new(),
+        };
+
+        let mut patterns = HashMap::new();
+        schema.check(&schema.root, &mut Path::root(), &mut patterns)?;
+        schema.patterns = patterns;
+
+        Ok(schema)
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    /// Validates `instance`, returning every violation (none if it is valid).
+    /// Each subschema is applied to each instance node at most once, however
+    /// often it is referred to.
+    pub fn validate(&self, instance: &Node) -> Vec<ValidationError> {
+        let mut errors = vec![];
+        self.validate_node(
+            &self.root,
+            &Path::root(),
+            instance,
+            &mut Path::root(),
+            &mut Memo::new(),
+            &mut errors,
+        );
+        errors
+    }
+
+    pub fn is_valid(&self, instance: &Node) -> bool {
+        self.validate(instance).is_empty()
+    }
+
+    fn check(
+        &self,
+        node: &Node,
+        path: &mut Path,
+        patterns: &mut HashMap<String, Regex>,
+    ) -> Result<(), SchemaError> {
+        let error = |message: String, path: &Path, node: &Node| SchemaError {
+            message,
+            schema_path: path.pointer(),
+            span: Some(*node.span()).filter(|span| !span.is_synthetic()),
+        };
+
+        let object = match node {
+            Node::Boolean(_) => return Ok(()),
+            Node::Object(object) => object,
+            _ => {
+                return Err(error(
+                    "a schema must be an object or a boolean".to_string(),
+                    path,
+                    node,
+                ))
+            }
+        };
+
+        for (keyword, value) in object.entries() {
+            path.push(PathSegment::Key(keyword.clone()));
+            match (keyword.as_str(), value) {
+                ("type", Node::String(name)) if TYPE_NAMES.contains(&name.value.as_str()) => {}
+                ("type", Node::Array(names))
+                    if names.value.iter().all(|name| {
+                        matches!(name, Node::String(name) if TYPE_NAMES.contains(&name.value.as_str()))
+                    }) => {}
+                ("type", _) => {
+                    return Err(error(
+                        format!("`type` must be one of {} or an array of them", TYPE_NAMES.join(", ")),
+                        path,
+                        value,
+                    ))
+                }
+                ("enum", Node::Array(_)) => {}
+                ("enum", _) => return Err(error("`enum` must be an array".to_string(), path, value)),
+                ("required", Node::Array(names)) if names.value.iter().all(Node::is_string) => {}
+                ("required", _) => {
+                    return Err(error("`required` must be an array of strings".to_string(), path, value))
+                }
+                ("pattern", Node::String(pattern)) => {
+                    let regex = Regex::new(&pattern.value)
+                        .map_err(|e| error(format!("invalid `pattern`: {}", e), path, value))?;
+                    patterns.insert(pattern.value.clone(), regex);
+                }
+                ("pattern", _) => return Err(error("`pattern` must be a string".to_string(), path, value)),
+                ("minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum", Node::Number(_)) => {}
+                ("minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum", _) => {
+                    return Err(error(format!("`{}` must be a number", keyword), path, value))
+                }
+                ("multipleOf", Node::Number(number)) if number.value.as_f64() > 0.0 => {}
+                ("multipleOf", _) => {
+                    return Err(error("`multipleOf` must be a number greater than 0".to_string(), path, value))
+                }
+                ("properties" | "$defs", Node::Object(schemas)) => {
+                    for (name, schema) in schemas.entries() {
+                        path.push(PathSegment::Key(name.clone()));
+                        self.check(schema, path, patterns)?;
+                        path.pop();
+                    }
+                }
+                ("properties" | "$defs", _) => {
+                    return Err(error(format!("`{}` must be an object", keyword), path, value))
+                }
+                ("items" | "additionalProperties", schema) => self.check(schema, path, patterns)?,
+                ("allOf" | "anyOf" | "oneOf", Node::Array(schemas)) if !schemas.value.is_empty() => {
+                    for (index, schema) in schemas.value.iter().enumerate() {
+                        path.push(PathSegment::Index(index));
+                        self.check(schema, path, patterns)?;
+                        path.pop();
+                    }
+                }
+                ("allOf" | "anyOf" | "oneOf", _) => {
+                    return Err(error(format!("`{}` must be a non-empty array", keyword), path, value))
+                }
+                ("$ref", Node::String(reference)) => {
+                    let (target, _) = self
+                        .resolve(&reference.value)
+                        .map_err(|message| error(message, path, value))?;
+                    if self.applies(target, node, &mut HashSet::new()) {
+                        return Err(error(
+                            format!("`$ref` to `{}` is cyclic", reference.value),
+                            path,
+                            value,
+                        ));
+                    }
+                }
+                ("$ref", _) => return Err(error("`$ref` must be a string".to_string(), path, value)),
+                _ => {}
+            }
+            path.pop();
+        }
+
+        Ok(())
+    }
+
+    /// Whether validating against `schema` validates the same instance
+    /// against `goal`, following `$ref`s and the subschemas of `allOf`,
+    /// `anyOf` and `oneOf` (but none that descend into the instance).
+    fn applies(&self, schema: &Node, goal: &Node, visited: &mut HashSet<*const Node>) -> bool {
+        if std::ptr::eq(schema, goal) {
+            return true;
+        }
+        if !visited.insert(schema) {
+            return false;
+        }
+
+        let object = match schema {
+            Node::Object(object) => object,
+            _ => return false,
+        };
+        object
+            .entries()
+            .any(|(keyword, value)| match (keyword.as_str(), value) {
+                ("allOf" | "anyOf" | "oneOf", Node::Array(schemas)) => schemas
+                    .value
+                    .iter()
+                    .any(|schema| self.applies(schema, goal, visited)),
+                // references not checked yet are reported when they are
+                ("$ref", Node::String(reference)) => self
+                    .resolve(&reference.value)
+                    .is_ok_and(|(target, _)| self.applies(target, goal, visited)),
+                _ => false,
+            })
+    }
+
+    /// Resolves a local reference like `#/$defs/port` to the referenced
+    /// schema and its path.
+    fn resolve(&self, reference: &str) -> Result<(&Node, Path), String> {
+        let fragment = reference
+            .strip_prefix('#')
+            .ok_or_else(|| format!("only local references are supported, found `{}`", reference))?;
+        let fragment = percent_decode(fragment)
+            .ok_or_else(|| format!("invalid percent-encoding in `{}`", reference))?;
+
+        let node = self
+            .root
+            .pointer(&fragment)
+            .map_err(|e| format!("could not resolve `{}`: {}", reference, e))?;
+
+        let mut path = Path::root();
+        for token in pointer::parse(&fragment)? {
+            path.push(PathSegment::Key(token));
+        }
+
+        Ok((node, path))
+    }
+
+    fn validate_node(
+        &self,
+        schema: &Node,
+        schema_path: &Path,
+        instance: &Node,
+        instance_path: &mut Path,
+        memo: &mut Memo,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let key: (*const Node, *const Node) = (schema, instance);
+        if let Some(cached) = memo.get(&key) {
+            errors.extend(cached.iter().cloned());
+            return;
+        }
+
+        let mut found = vec![];
+        self.apply(
+            schema,
+            schema_path,
+            instance,
+            instance_path,
+            memo,
+            &mut found,
+        );
+        // a subschema referred to more than once (e.g. from both branches of
+        // an `allOf`) reports its errors only once
+        let mut seen = HashSet::new();
+        found.retain(|error| {
+            seen.insert((
+                error.schema_path.clone(),
+                error.instance_path.pointer(),
+                error.message.clone(),
+            ))
+        });
+        errors.extend(found.iter().cloned());
+        memo.insert(key, found);
+    }
+
+    fn apply(
+        &self,
+        schema: &Node,
+        schema_path: &Path,
+        instance: &Node,
+        instance_path: &mut Path,
+        memo: &mut Memo,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let object = match schema {
+            Node::Boolean(node) if node.value => return,
+            Node::Boolean(_) => {
+                errors.push(self.error(
+                    "no value is allowed here".to_string(),
+                    schema_path,
+                    schema,
+                    instance_path,
+                    instance,
+                ));
+                return;
+            }
+            Node::Object(object) => object,
+            // anything else was already rejected by `Schema::new`
+            _ => return,
+        };
+
+        for (keyword, value) in object.entries() {
+            let keyword_path = schema_path.child(PathSegment::Key(keyword.clone()));
+            let mut fail = |message: String| {
+                errors.push(self.error(message, &keyword_path, value, instance_path, instance))
+            };
+
+            match (keyword.as_str(), value, instance) {
+                ("type", _, _) => {
+                    let names: Vec<&str> = match value {
+                        Node::String(name) => vec![&name.value],
+                        Node::Array(names) => {
+                            names.value.iter().filter_map(|n| n.as_str().ok()).collect()
+                        }
+                        _ => vec![],
+                    };
+                    if !names.iter().any(|name| has_type(instance, name)) {
+                        fail(format!(
+                            "expected {}, found {}",
+                            names.join(" or "),
+                            instance.type_name()
+                        ));
+                    }
+                }
+                ("enum", Node::Array(options), _)
+                    if !options
+                        .value
+                        .iter()
+                        .any(|option| option.semantic_eq(instance)) =>
+                {
+                    fail("value is not one of the allowed values".to_string());
+                }
+                ("const", _, _) if !value.semantic_eq(instance) => {
+                    fail(format!("expected {}", value.raw()));
+                }
+                ("required", Node::Array(names), Node::Object(members)) => {
+                    for name in names.value.iter().filter_map(|n| n.as_str().ok()) {
+                        if !members.contains_key(name) {
+                            fail(format!("missing required property `{}`", name));
+                        }
+                    }
+                }
+                ("pattern", Node::String(pattern), Node::String(string)) => {
+                    let matches = self
+                        .patterns
+                        .get(&pattern.value)
+                        .is_some_and(|regex| regex.is_match(&string.value));
+                    if !matches {
+                        fail(format!("string does not match pattern `{}`", pattern.value));
+                    }
+                }
+                ("minimum", Node::Number(bound), Node::Number(number))
+                    if compare(&number.value, &bound.value).is_lt() =>
+                {
+                    fail(format!(
+                        "{} is less than the minimum of {}",
+                        number.raw, bound.raw
+                    ));
+                }
+                ("maximum", Node::Number(bound), Node::Number(number))
+                    if compare(&number.value, &bound.value).is_gt() =>
+                {
+                    fail(format!(
+                        "{} is greater than the maximum of {}",
+                        number.raw, bound.raw
+                    ));
+                }
+                ("exclusiveMinimum", Node::Number(bound), Node::Number(number))
+                    if compare(&number.value, &bound.value).is_le() =>
+                {
+                    fail(format!("{} is not greater than {}", number.raw, bound.raw));
+                }
+                ("exclusiveMaximum", Node::Number(bound), Node::Number(number))
+                    if compare(&number.value, &bound.value).is_ge() =>
+                {
+                    fail(format!("{} is not less than {}", number.raw, bound.raw));
+                }
+                ("multipleOf", Node::Number(divisor), Node::Number(number))
+                    if !is_multiple_of(&number.value, &divisor.value) =>
+                {
+                    fail(format!(
+                        "{} is not a multiple of {}",
+                        number.raw, divisor.raw
+                    ));
+                }
+                ("properties", Node::Object(schemas), Node::Object(members)) => {
+                    for (name, schema) in schemas.entries() {
+                        if let Some(member) = members.get(name) {
+                            instance_path.push(PathSegment::Key(name.clone()));
+                            self.validate_node(
+                                schema,
+                                &keyword_path.child(PathSegment::Key(name.clone())),
+                                member,
+                                instance_path,
+                                memo,
+                                errors,
+                            );
+                            instance_path.pop();
+                        }
+                    }
+                }
+                ("additionalProperties", _, Node::Object(members)) => {
+                    let known = match object.get("properties") {
+                        Some(Node::Object(properties)) => Some(properties),
+                        _ => None,
+                    };
+                    for (name, member) in members.entries() {
+                        if known.is_some_and(|known| known.contains_key(name)) {
+                            continue;
+                        }
+
+                        instance_path.push(PathSegment::Key(name.clone()));
+                        self.validate_node(
+                            value,
+                            &keyword_path,
+                            member,
+                            instance_path,
+                            memo,
+                            errors,
+                        );
+                        instance_path.pop();
+                    }
+                }
+                ("items", _, Node::Array(items)) => {
+                    for (index, item) in items.value.iter().enumerate() {
+                        instance_path.push(PathSegment::Index(index));
+                        self.validate_node(value, &keyword_path, item, instance_path, memo, errors);
+                        instance_path.pop();
+                    }
+                }
+                ("allOf", Node::Array(schemas), _) => {
+                    for (index, schema) in schemas.value.iter().enumerate() {
+                        let path = keyword_path.child(PathSegment::Index(index));
+                        self.validate_node(schema, &path, instance, instance_path, memo, errors);
+                    }
+                }
+                ("anyOf", Node::Array(schemas), _) => {
+                    let valid = self.count_valid(
+                        schemas.value.iter(),
+                        &keyword_path,
+                        instance,
+                        instance_path,
+                        memo,
+                    );
+                    if valid == 0 {
+                        fail("value does not match any of the schemas in `anyOf`".to_string());
+                    }
+                }
+                ("oneOf", Node::Array(schemas), _) => {
+                    let valid = self.count_valid(
+                        schemas.value.iter(),
+                        &keyword_path,
+                        instance,
+                        instance_path,
+                        memo,
+                    );
+                    if valid != 1 {
+                        fail(format!(
+                            "value must match exactly one of the schemas in `oneOf`, but matches {}",
+                            valid
+                        ));
+                    }
+                }
+                ("$ref", Node::String(reference), _) => {
+                    // references were verified (and found acyclic) by `Schema::new`
+                    if let Ok((target, target_path)) = self.resolve(&reference.value) {
+                        self.validate_node(
+                            target,
+                            &target_path,
+                            instance,
+                            instance_path,
+                            memo,
+                            errors,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn count_valid<'n>(
+        &self,
+        schemas: impl Iterator<Item = &'n Node>,
+        schema_path: &Path,
+        instance: &Node,
+        instance_path: &Path,
+        memo: &mut Memo,
+    ) -> usize {
+        schemas
+            .enumerate()
+            .filter(|(index, schema)| {
+                let mut errors = vec![];
+                let path = schema_path.child(PathSegment::Index(*index));
+                self.validate_node(
+                    schema,
+                    &path,
+                    instance,
+                    &mut instance_path.clone(),
+                    memo,
+                    &mut errors,
+                );
+                errors.is_empty()
+            })
+            .count()
+    }
+
+    fn error(
+        &self,
+        message: String,
+        schema_path: &Path,
+        schema: &Node,
+        instance_path: &Path,
+        instance: &Node,
+    ) -> ValidationError {
+        let located = |span: &Span| Some(*span).filter(|span| !span.is_synthetic());
+        ValidationError {
+            message,
+            instance_path: instance_path.clone(),
+            instance_span: located(instance.span()),
+            schema_path: schema_path.pointer(),
+            schema_span: located(schema.span()),
+        }
+    }
+}
+
+fn has_type(node: &Node, name: &str) -> bool {
+    match (name, node) {
+        ("null", Node::Null(_))
+        | ("boolean", Node::Boolean(_))
+        | ("number", Node::Number(_))
+        | ("string", Node::String(_))
+        | ("array", Node::Array(_))
+        | ("object", Node::Object(_)) => true,
+        // numbers with a zero fractional part are integers as well
+        ("integer", Node::Number(number)) => match number.value {
+            NumberNodeValue::Int(_) => true,
+            NumberNodeValue::Float(value) => value.fract() == 0.0,
+        },
+        _ => false,
+    }
+}
+
+fn compare(a: &NumberNodeValue, b: &NumberNodeValue) -> std::cmp::Ordering {
+    match (a, b) {
+        (NumberNodeValue::Int(a), NumberNodeValue::Int(b)) => a.cmp(b),
+        (a, b) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(std::cmp::Ordering::Equal),
+    }
+}
+
+fn is_multiple_of(number: &NumberNodeValue, divisor: &NumberNodeValue) -> bool {
+    match (number, divisor) {
+        (NumberNodeValue::Int(number), NumberNodeValue::Int(divisor)) => number % divisor == 0,
+        (number, divisor) => {
+            let quotient = number.as_f64() / divisor.as_f64();
+            (quotient - quotient.round()).abs() < 1e-9
+        }
+    }
+}
+
+/// Decodes the `%XX` escapes of a URI fragment.
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    const SCHEMA: &str = "{
+        \"type\": \"object\",
+        \"required\": [\"name\", \"server\"],
+        \"properties\": {
+            \"name\": {\"type\": \"string\", \"pattern\": \"^[a-z]+$\"},
+            \"server\": {\"$ref\": \"#/$defs/server\"},
+            \"mode\": {\"enum\": [\"fast\", \"slow\"]},
+            \"tags\": {\"type\": \"array\", \"items\": {\"type\": \"string\"}},
+            \"id\": {\"oneOf\": [{\"type\": \"integer\"}, {\"type\": \"number\", \"minimum\": 10}]}
+        },
+        \"$defs\": {
+            \"server\": {
+                \"type\": \"object\",
+                \"properties\": {\"port\": {\"type\": \"integer\", \"minimum\": 1, \"maximum\": 65535}},
+                \"additionalProperties\": false
+            }
+        }
+    }";
+
+    #[test]
+    fn it_validates_instances() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+
+        assert!(schema.is_valid(&parse_source(
+            "{\"name\": \"app\", \"server\": {\"port\": 80}, \"mode\": \"fast\", \"tags\": [\"a\"], \"id\": 20.5}"
+        )));
+
+        let instance = parse_source(
+            "{\"name\": \"App\", \"server\": {\"port\": 70000, \"host\": \"h\"}, \"mode\": \"medium\", \"tags\": [1], \"id\": 12}",
+        );
+        let mut errors: Vec<_> = schema
+            .validate(&instance)
+            .into_iter()
+            .map(|e| (e.instance_path.pointer(), e.schema_path))
+            .collect();
+        errors.sort();
+
+        assert_eq!(
+            errors,
+            vec![
+                ("/id".to_string(), "/properties/id/oneOf".to_string()),
+                ("/mode".to_string(), "/properties/mode/enum".to_string()),
+                ("/name".to_string(), "/properties/name/pattern".to_string()),
+                (
+                    "/server/host".to_string(),
+                    "/$defs/server/additionalProperties".to_string()
+                ),
+                (
+                    "/server/port".to_string(),
+                    "/$defs/server/properties/port/maximum".to_string()
+                ),
+                (
+                    "/tags/0".to_string(),
+                    "/properties/tags/items/type".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_locates_errors() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+
+        let errors = schema.validate(&parse_source("{\n  \"name\": 1\n}"));
+        assert_eq!(errors.len(), 2);
+        let error = errors
+            .iter()
+            .find(|e| e.instance_path.depth() == 1)
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "expected string, found number at /name, line 2 col 11 (schema `/properties/name/type`)"
+        );
+        assert_eq!(error.schema_span.unwrap().start().line(), 5);
+    }
+
+    #[test]
+    fn it_rejects_invalid_schemas() {
+        let error =
+            Schema::parse("{\n  \"properties\": {\"a\": {\"type\": \"text\"}}\n}").unwrap_err();
+        assert_eq!(error.schema_path, "/properties/a/type");
+        assert_eq!(error.span.unwrap().start().line(), 2);
+
+        assert!(Schema::parse("{\"$ref\": \"#/$defs/missing\"}").is_err());
+        assert!(Schema::parse("{\"pattern\": \"(\"}").is_err());
+        assert!(Schema::parse("{\"oneOf\": []}").is_err());
+    }
+
+    #[test]
+    fn it_rejects_reference_cycles() {
+        let error = Schema::parse("{\"$ref\": \"#\"}").unwrap_err();
+        assert_eq!(error.message, "`$ref` to `#` is cyclic");
+        assert_eq!(error.schema_path, "/$ref");
+
+        let error =
+            Schema::parse("{\"allOf\": [{\"$ref\": \"#\"}, {\"$ref\": \"#\"}]}").unwrap_err();
+        assert_eq!(error.schema_path, "/allOf/0/$ref");
+        assert!(Schema::parse(
+            "{\"$defs\": {\"a\": {\"anyOf\": [{\"$ref\": \"#/$defs/b\"}]}, \"b\": {\"oneOf\": [true, {\"$ref\": \"#/$defs/a\"}]}}}"
+        )
+        .is_err());
+
+        // references that descend into the instance are fine
+        let schema =
+            Schema::parse("{\"anyOf\": [{\"type\": \"integer\"}, {\"type\": \"array\", \"items\": {\"$ref\": \"#\"}}]}")
+                .unwrap();
+        assert!(schema.is_valid(&parse_source("[1, [2, [3]]]")));
+        assert!(!schema.is_valid(&parse_source("[1, [\"a\"]]")));
+    }
+
+    #[test]
+    fn it_applies_shared_subschemas_once() {
+        // each definition refers to the next one twice, which would validate
+        // the last one 2^40 times without memoizing
+        let defs: Vec<_> = (0..40)
+            .map(|index| {
+                format!(
+                    "\"d{0}\": {{\"allOf\": [{{\"$ref\": \"#/$defs/d{1}\"}}, {{\"$ref\": \"#/$defs/d{1}\"}}]}}",
+                    index,
+                    index + 1
+                )
+            })
+            .collect();
+        let schema = Schema::parse(&format!(
+            "{{\"$ref\": \"#/$defs/d0\", \"$defs\": {{{}, \"d40\": {{\"type\": \"string\"}}}}}}",
+            defs.join(", ")
+        ))
+        .unwrap();
+
+        assert!(schema.is_valid(&parse_source("\"a\"")));
+        let errors = schema.validate(&parse_source("1"));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].schema_path, "/$defs/d40/type");
+    }
+}
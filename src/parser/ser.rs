@@ -0,0 +1,586 @@
+use std::fmt;
+
+use serde::ser::{self, Impossible, Serialize};
+
+use super::de::Error;
+use super::node::Node;
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Error {
+        <Error as serde::de::Error>::custom(message)
+    }
+}
+
+/// Serializes any `T` into a [`Node`] tree. All of the produced nodes have
+/// synthetic spans, and the members of objects are in the order they were
+/// serialized in (e.g. the order of the fields of a struct).
+pub fn to_node<T: Serialize + ?Sized>(value: &T) -> Result<Node, Error> {
+    value.serialize(Serializer)
+}
+
+/// A [`serde::Serializer`] producing [`Node`] trees.
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Node;
+    type Error = Error;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeObject;
+    type SerializeStruct = SerializeObject;
+    type SerializeStructVariant = SerializeObject;
+
+    fn serialize_bool(self, value: bool) -> Result<Node, Error> {
+        Ok(Node::boolean(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<Node, Error> {
+        self.serialize_i64(value as i64)
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<Node, Error> {
+        self.serialize_i64(value as i64)
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<Node, Error> {
+        self.serialize_i64(value as i64)
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<Node, Error> {
+        Ok(Node::int(value))
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<Node, Error> {
+        i64::try_from(value)
+            .map(Node::int)
+            .map_err(|_| ser::Error::custom(format!("number `{}` is out of range", value)))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<Node, Error> {
+        self.serialize_i64(value as i64)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<Node, Error> {
+        self.serialize_i64(value as i64)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<Node, Error> {
+        self.serialize_i64(value as i64)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<Node, Error> {
+        self.serialize_i128(value as i128)
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<Node, Error> {
+        i64::try_from(value)
+            .map(Node::int)
+            .map_err(|_| ser::Error::custom(format!("number `{}` is out of range", value)))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<Node, Error> {
+        self.serialize_f64(value as f64)
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<Node, Error> {
+        if !value.is_finite() {
+            return Err(ser::Error::custom(format!(
+                "number `{}` cannot be represented in JSON",
+                value
+            )));
+        }
+
+        Ok(Node::float(value))
+    }
+
+    fn serialize_char(self, value: char) -> Result<Node, Error> {
+        Ok(Node::string(value.encode_utf8(&mut [0; 4])))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<Node, Error> {
+        Ok(Node::string(value))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<Node, Error> {
+        Ok(Node::array(
+            value.iter().map(|&b| Node::int(b as i64)).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Node, Error> {
+        Ok(Node::null())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Node, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Node, Error> {
+        Ok(Node::null())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Node, Error> {
+        Ok(Node::null())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Node, Error> {
+        Ok(Node::string(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Node, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Node, Error> {
+        Ok(Node::object(vec![(variant.to_string(), to_node(value)?)]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeObject, Error> {
+        Ok(SerializeObject {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeObject, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeObject, Error> {
+        Ok(SerializeObject {
+            entries: Vec::with_capacity(len),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+/// Wraps the content of a non-unit enum variant as `{"Variant": content}`.
+fn wrap_variant(variant: Option<&'static str>, node: Node) -> Node {
+    match variant {
+        Some(variant) => Node::object(vec![(variant.to_string(), node)]),
+        None => node,
+    }
+}
+
+pub struct SerializeArray {
+    items: Vec<Node>,
+    variant: Option<&'static str>,
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(to_node(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Ok(wrap_variant(self.variant, Node::array(self.items)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+pub struct SerializeObject {
+    entries: Vec<(String, Node)>,
+    key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl ser::SerializeMap for SerializeObject {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("value serialized before its key"))?;
+        self.entries.push((key, to_node(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Ok(wrap_variant(self.variant, Node::object(self.entries)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeObject {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.entries.push((key.to_string(), to_node(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeObject {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// Serializes object keys, which have to be strings in JSON. Like most JSON
+/// libraries, integer, boolean and char keys are converted to strings.
+struct KeySerializer;
+
+fn key_error() -> Error {
+    ser::Error::custom("object keys must be strings")
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    fn serialize_bool(self, value: bool) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_f32(self, _value: f32) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_f64(self, _value: f64) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_char(self, value: char) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_str(self, value: &str) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(key_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::parser::de::from_node;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Document {
+        name: String,
+        version: u32,
+        ratio: f64,
+        tags: Vec<String>,
+        kind: Kind,
+        extra: Option<BTreeMap<u8, bool>>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    enum Kind {
+        Plain,
+        Sized(u8, u8),
+    }
+
+    #[test]
+    fn it_serializes_to_nodes() {
+        let mut extra = BTreeMap::new();
+        extra.insert(1, true);
+        let document = Document {
+            name: "a \"quoted\" name".to_string(),
+            version: 2,
+            ratio: 0.5,
+            tags: vec!["x".to_string()],
+            kind: Kind::Sized(1, 2),
+            extra: Some(extra),
+        };
+
+        let node = to_node(&document).unwrap();
+        assert!(node.span().is_synthetic());
+        assert_eq!(
+            node.raw(),
+            "{\"name\":\"a \\\"quoted\\\" name\",\"version\":2,\"ratio\":0.5,\"tags\":[\"x\"],\"kind\":{\"Sized\":[1,2]},\"extra\":{\"1\":true}}"
+        );
+        assert!(node.pointer("/kind/Sized/1").unwrap().span().is_synthetic());
+
+        assert_eq!(to_node(&Kind::Plain).unwrap().raw(), "\"Plain\"");
+        assert!(to_node(&f64::NAN).is_err());
+        assert!(to_node(&u64::MAX).is_err());
+    }
+
+    #[test]
+    fn it_round_trips_through_the_deserializer() {
+        let document = Document {
+            name: "name".to_string(),
+            version: 1,
+            ratio: 2.0,
+            tags: vec![],
+            kind: Kind::Plain,
+            extra: None,
+        };
+
+        let node = to_node(&document).unwrap();
+        assert_eq!(from_node::<Document>(&node).unwrap(), document);
+    }
+}
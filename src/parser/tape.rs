@@ -0,0 +1,486 @@
+use std::collections::HashSet;
+
+use super::access::{parse_path, AccessError};
+use super::node::{
+    ArrayNode, BooleanNode, Node, NullNode, NumberNode, NumberNodeValue, ObjectNode, Span,
+    StringNode,
+};
+use super::pull::{PullParser, RawEvent};
+use super::tokenizer::TokenKind;
+use super::visit::{Path, PathSegment};
+use super::{boolean_value, number_value, string_value, Parser};
+
+#[derive(Debug, PartialEq, Clone)]
+enum Kind {
+    Null,
+    Boolean(bool),
+    Number(NumberNodeValue),
+    String,
+    Array,
+    Object,
+    /// An object key, always followed by the entries of its value.
+    Key,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    kind: Kind,
+    span: Span,
+    /// The index of the entry following this value and all of its contents.
+    next: usize,
+    /// The number of items or members of a container.
+    len: usize,
+}
+
+/// A document stored as a flat list of entries in document order, rather than
+/// as a tree of [`Node`]s: containers are followed by their contents and know
+/// where those end, so that they can be skipped over. Raw text and strings
+/// are borrowed from the source, so parsing a document takes a handful of
+/// allocations however large it is.
+///
+/// Objects keep all of their members, including those with duplicate keys;
+/// like for [`Node`], the last one wins when looking up a key.
+#[derive(Debug, Clone)]
+pub struct Tape<'source> {
+    source: &'source str,
+    entries: Vec<Entry>,
+}
+
+impl<'source> Tape<'source> {
+    pub fn root(&self) -> TapeNode<'_, 'source> {
+        TapeNode {
+            tape: self,
+            index: 0,
+        }
+    }
+}
+
+impl<'source> Parser<'source> {
+    /// Parses `source` like [`Parser::parse_str`], into a [`Tape`].
+    pub fn parse_tape(&mut self, source: &'source str) -> Result<Tape<'source>, String> {
+        let mut events = PullParser::new(source, self.tokenizer.comments())
+            .max_depth(self.max_depth)
+            .limits(self.limits);
+        let mut entries: Vec<Entry> = vec![];
+        // the indices of the containers that are still open
+        let mut open: Vec<usize> = vec![];
+        // the keys of the open objects, if duplicates are denied
+        let mut keys: Vec<HashSet<&str>> = vec![];
+
+        while let Some(event) = events.next_raw()? {
+            let index = entries.len();
+            let (kind, span) = match event {
+                RawEvent::EndObject(span) | RawEvent::EndArray(span) => {
+                    if let Some(start) = open.pop() {
+                        entries[start].span = span;
+                        entries[start].next = index;
+                    }
+                    if matches!(event, RawEvent::EndObject(_)) && self.deny_duplicate_keys {
+                        keys.pop();
+                    }
+                    continue;
+                }
+                RawEvent::Key(token) => {
+                    let key = string_value(token.raw());
+                    if self.deny_duplicate_keys {
+                        let unique = keys.last_mut().is_none_or(|keys| keys.insert(key));
+                        if !unique {
+                            let start = token.span().start();
+                            return Err(format!(
+                                "duplicate key `{}` at line {} col {}",
+                                key,
+                                start.line(),
+                                start.column() + 1
+                            ));
+                        }
+                    }
+                    (Kind::Key, *token.span())
+                }
+                RawEvent::StartArray(span) => (Kind::Array, span),
+                RawEvent::StartObject(span) => (Kind::Object, span),
+                RawEvent::Scalar(token) => {
+                    let raw = token.raw();
+                    let kind = match token.kind() {
+                        TokenKind::NullLiteral => Kind::Null,
+                        TokenKind::BooleanLiteral => Kind::Boolean(boolean_value(raw)),
+                        TokenKind::NumberLiteral => Kind::Number(number_value(&token)?),
+                        _ => Kind::String,
+                    };
+                    (kind, *token.span())
+                }
+            };
+
+            // arrays count their values, objects their keys
+            if let Some(&parent) = open.last() {
+                if (entries[parent].kind == Kind::Array) != (kind == Kind::Key) {
+                    entries[parent].len += 1;
+                }
+            }
+
+            if kind == Kind::Object && self.deny_duplicate_keys {
+                keys.push(HashSet::new());
+            }
+            if matches!(kind, Kind::Array | Kind::Object) {
+                open.push(index);
+            }
+
+            entries.push(Entry {
+                kind,
+                span,
+                next: index + 1,
+                len: 0,
+            });
+        }
+
+        Ok(Tape { source, entries })
+    }
+}
+
+/// A value within a [`Tape`], offering the same accessors as
+/// [`NodeRef`](super::access::NodeRef). The path reported in errors is only
+/// worked out when an error occurs.
+#[derive(Debug, Clone, Copy)]
+pub struct TapeNode<'t, 'source> {
+    tape: &'t Tape<'source>,
+    index: usize,
+}
+
+impl<'t, 'source> TapeNode<'t, 'source> {
+    fn entry(&self) -> &'t Entry {
+        &self.tape.entries[self.index]
+    }
+
+    fn at(&self, index: usize) -> TapeNode<'t, 'source> {
+        TapeNode {
+            tape: self.tape,
+            index,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.entry().span
+    }
+
+    pub fn raw(&self) -> &'source str {
+        let span = self.span();
+        &self.tape.source[span.start().cursor()..span.end().cursor()]
+    }
+
+    /// The name of this value's type, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self.entry().kind {
+            Kind::Null => "null",
+            Kind::Boolean(_) => "boolean",
+            Kind::Number(_) => "number",
+            Kind::String | Kind::Key => "string",
+            Kind::Array => "array",
+            Kind::Object => "object",
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.entry().kind == Kind::Null
+    }
+
+    pub fn is_boolean(&self) -> bool {
+        matches!(self.entry().kind, Kind::Boolean(_))
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self.entry().kind, Kind::Number(_))
+    }
+
+    pub fn is_string(&self) -> bool {
+        self.entry().kind == Kind::String
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.entry().kind == Kind::Array
+    }
+
+    pub fn is_object(&self) -> bool {
+        self.entry().kind == Kind::Object
+    }
+
+    /// The number of items or members of a container, zero otherwise.
+    pub fn len(&self) -> usize {
+        self.entry().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The items of an array, nothing otherwise.
+    pub fn items(&self) -> impl Iterator<Item = TapeNode<'t, 'source>> {
+        let entry = self.entry();
+        let (mut index, end) = match entry.kind {
+            Kind::Array => (self.index + 1, entry.next),
+            _ => (0, 0),
+        };
+        let node = *self;
+
+        std::iter::from_fn(move || {
+            let item = (index < end).then(|| node.at(index))?;
+            index = item.entry().next;
+            Some(item)
+        })
+    }
+
+    /// The keys and values of an object in source order, nothing otherwise.
+    pub fn members(&self) -> impl Iterator<Item = (&'source str, TapeNode<'t, 'source>)> {
+        let entry = self.entry();
+        let (mut index, end) = match entry.kind {
+            Kind::Object => (self.index + 1, entry.next),
+            _ => (0, 0),
+        };
+        let node = *self;
+
+        std::iter::from_fn(move || {
+            let key = (index < end).then(|| node.at(index))?;
+            let value = node.at(index + 1);
+            index = value.entry().next;
+            Some((string_value(key.raw()), value))
+        })
+    }
+
+    /// The path from the root of the tape to this value.
+    pub fn path(&self) -> Path {
+        let mut path = Path::root();
+        let mut current = self.tape.root();
+
+        while current.index != self.index {
+            let mut children: Vec<(PathSegment, TapeNode)> = match current.entry().kind {
+                Kind::Array => current
+                    .items()
+                    .enumerate()
+                    .map(|(index, item)| (PathSegment::Index(index), item))
+                    .collect(),
+                _ => current
+                    .members()
+                    .map(|(key, value)| (PathSegment::Key(key.to_string()), value))
+                    .collect(),
+            };
+            children
+                .retain(|(_, child)| child.index <= self.index && self.index < child.entry().next);
+
+            match children.pop() {
+                Some((segment, child)) => {
+                    path.push(segment);
+                    current = child;
+                }
+                None => break,
+            }
+        }
+
+        path
+    }
+
+    fn error(&self, message: String, path: Path) -> AccessError {
+        AccessError {
+            message,
+            path,
+            found: Some(self.type_name()),
+            span: Some(self.span()),
+        }
+    }
+
+    fn mismatch(&self, expected: &str) -> AccessError {
+        self.error(format!("expected {}", expected), self.path())
+    }
+
+    /// Gets the value of the given object member.
+    pub fn get(&self, key: &str) -> Result<TapeNode<'t, 'source>, AccessError> {
+        if !self.is_object() {
+            let path = self.path().child(PathSegment::Key(key.to_string()));
+            return Err(self.error("expected object".to_string(), path));
+        }
+
+        // like in a `Node`, the last member with a given key wins
+        match self.members().filter(|(member, _)| *member == key).last() {
+            Some((_, value)) => Ok(value),
+            None => Err(self.error(
+                format!("missing key `{}`", key),
+                self.path().child(PathSegment::Key(key.to_string())),
+            )),
+        }
+    }
+
+    /// Gets the array item at the given index.
+    pub fn index(&self, index: usize) -> Result<TapeNode<'t, 'source>, AccessError> {
+        let path = || self.path().child(PathSegment::Index(index));
+        if !self.is_array() {
+            return Err(self.error("expected array".to_string(), path()));
+        }
+
+        self.items().nth(index).ok_or_else(|| {
+            self.error(
+                format!(
+                    "index {} out of bounds for array of length {}",
+                    index,
+                    self.len()
+                ),
+                path(),
+            )
+        })
+    }
+
+    /// Follows a simple dotted path like `a.b[0].c`.
+    pub fn get_path(&self, path: &str) -> Result<TapeNode<'t, 'source>, AccessError> {
+        let mut current = *self;
+        for segment in parse_path(path).map_err(|message| AccessError {
+            message,
+            path: self.path(),
+            found: None,
+            span: None,
+        })? {
+            current = match segment {
+                PathSegment::Key(key) => current.get(&key)?,
+                PathSegment::Index(index) => current.index(index)?,
+            };
+        }
+
+        Ok(current)
+    }
+
+    pub fn as_null(&self) -> Result<(), AccessError> {
+        match self.entry().kind {
+            Kind::Null => Ok(()),
+            _ => Err(self.mismatch("null")),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, AccessError> {
+        match self.entry().kind {
+            Kind::Boolean(value) => Ok(value),
+            _ => Err(self.mismatch("boolean")),
+        }
+    }
+
+    pub fn as_i64(&self) -> Result<i64, AccessError> {
+        match self.entry().kind {
+            Kind::Number(NumberNodeValue::Int(value)) => Ok(value),
+            _ => Err(self.mismatch("integer")),
+        }
+    }
+
+    /// Integers are converted to floats, which may lose precision.
+    pub fn as_f64(&self) -> Result<f64, AccessError> {
+        match &self.entry().kind {
+            Kind::Number(value) => Ok(value.as_f64()),
+            _ => Err(self.mismatch("number")),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&'source str, AccessError> {
+        match self.entry().kind {
+            Kind::String => Ok(string_value(self.raw())),
+            _ => Err(self.mismatch("string")),
+        }
+    }
+
+    /// Builds the [`Node`] tree of this value.
+    pub fn to_node(&self) -> Node {
+        let span = self.span();
+        // containers do not keep their raw text
+        let raw = || self.raw().to_string();
+        match &self.entry().kind {
+            Kind::Null => Node::Null(NullNode { span, raw: raw() }),
+            Kind::Boolean(value) => Node::Boolean(BooleanNode {
+                span,
+                value: *value,
+                raw: raw(),
+            }),
+            Kind::Number(value) => Node::Number(NumberNode {
+                span,
+                value: value.clone(),
+                raw: raw(),
+            }),
+            Kind::String | Kind::Key => Node::String(StringNode {
+                span,
+                value: string_value(self.raw()).to_string(),
+                raw: raw(),
+            }),
+            Kind::Array => Node::Array(ArrayNode {
+                span,
+                value: self.items().map(|item| item.to_node()).collect(),
+            }),
+            Kind::Object => {
+                let mut object = ObjectNode::empty(span);
+                for (key, member) in self.members() {
+                    let key_node = self.at(member.index - 1);
+                    let key = StringNode {
+                        span: key_node.span(),
+                        value: key.to_string(),
+                        raw: key_node.raw().to_string(),
+                    };
+                    object.insert(key, member.to_node());
+                }
+
+                Node::Object(object)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str =
+        "{\"a\": [1, 2.5, \"x\", [true, null]],\n \"b\": {\"c\": {}, \"d\": []}, \"a\": [3]}";
+
+    #[test]
+    fn it_reads_like_a_tree() {
+        let tape = Parser::new_without_comments().parse_tape(SOURCE).unwrap();
+        let root = tape.root();
+        assert_eq!(root.to_node(), SOURCE.parse::<Node>().unwrap());
+
+        assert_eq!(root.len(), 3);
+        let keys: Vec<_> = root.members().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec!["a", "b", "a"]);
+        assert_eq!(root.get("a").unwrap().raw(), "[3]");
+
+        let b = root.get("b").unwrap();
+        assert!(b.get("c").unwrap().is_empty());
+        assert_eq!(b.get("d").unwrap().items().count(), 0);
+        assert_eq!(b.span().start().line(), 2);
+
+        let first = root.members().next().unwrap().1;
+        let items: Vec<_> = first.items().map(|item| item.type_name()).collect();
+        assert_eq!(items, vec!["number", "number", "string", "array"]);
+        assert_eq!(first.index(2).unwrap().as_str(), Ok("x"));
+        assert_eq!(first.get_path("[3][0]").unwrap().as_bool(), Ok(true));
+    }
+
+    #[test]
+    fn it_reports_errors_like_node_ref() {
+        let tape = Parser::new_without_comments().parse_tape(SOURCE).unwrap();
+        let node = SOURCE.parse::<Node>().unwrap();
+
+        for path in ["b.c.e", "b.d[1]", "a[0].x", "b.c", "b[0]"] {
+            let expected = node.get_path(path).and_then(|node| node.as_i64());
+            let actual = tape.root().get_path(path).and_then(|node| node.as_i64());
+            assert_eq!(
+                actual.map_err(|error| error.to_string()),
+                expected.map_err(|error| error.to_string()),
+                "reading {}",
+                path
+            );
+        }
+
+        assert_eq!(
+            Parser::new_without_comments()
+                .deny_duplicate_keys()
+                .parse_tape(SOURCE)
+                .unwrap_err(),
+            "duplicate key `a` at line 2 col 27"
+        );
+        assert_eq!(
+            Parser::new_without_comments()
+                .parse_tape("[1 2]")
+                .unwrap_err(),
+            "[1 2]".parse::<Node>().unwrap_err()
+        );
+    }
+}
@@ -1,7 +1,12 @@
 use regex::Regex;
 
+use super::limits::Limits;
+use super::located;
 use super::node::{Position, Span};
 
+/// How many characters of the input an unrecognized token error shows.
+const EXCERPT_LENGTH: usize = 16;
+
 pub struct TokenSpec {
     kind: TokenKind,
     regex: Regex,
@@ -15,8 +20,8 @@ impl TokenSpec {
         }
     }
 
-    fn test(&self, s: &String) -> Option<String> {
-        self.regex.find(s).map(|m| m.as_str().to_string())
+    fn test<'s>(&self, s: &'s str) -> Option<&'s str> {
+        self.regex.find(s).map(|m| m.as_str())
     }
 }
 
@@ -40,15 +45,16 @@ pub enum TokenKind {
     StringLiteral,
 }
 
+/// A token, borrowing its raw text from the source.
 #[derive(Debug)]
-pub struct Token {
+pub struct Token<'source> {
     kind: TokenKind,
-    raw: String,
+    raw: &'source str,
     span: Span,
 }
 
-impl Token {
-    pub fn new(kind: TokenKind, raw: String, span: Span) -> Token {
+impl<'source> Token<'source> {
+    pub fn new(kind: TokenKind, raw: &'source str, span: Span) -> Token<'source> {
         Token { kind, raw, span }
     }
 
@@ -56,8 +62,8 @@ impl Token {
         &self.kind
     }
 
-    pub fn raw(&self) -> &String {
-        &self.raw
+    pub fn raw(&self) -> &'source str {
+        self.raw
     }
 
     pub fn span(&self) -> &Span {
@@ -68,8 +74,15 @@ impl Token {
 pub struct Tokenizer<'source> {
     specs: Vec<TokenSpec>,
     position: Position,
-    source: Option<&'source String>,
+    source: Option<&'source str>,
+    // the number of bytes of input before `source`, which have been dropped
+    offset: usize,
+    // whether more input may follow `source`
+    partial: bool,
     comments: bool,
+    limits: Limits,
+    // the number of significant tokens so far
+    tokens: usize,
 }
 
 impl<'source> Tokenizer<'source> {
@@ -96,34 +109,99 @@ impl<'source> Tokenizer<'source> {
                 // look-around not supported
                 // TokenSpec::new(TokenKind::BooleanLiteral, r"^\btrue(?!\w|\$)"),
                 TokenSpec::new(TokenKind::BooleanLiteral, r"^\btrue\b"),
-                TokenSpec::new(TokenKind::NumberLiteral, r"^\d+\.\d+"),
-                TokenSpec::new(TokenKind::NumberLiteral, r"^\d+"),
+                TokenSpec::new(TokenKind::NumberLiteral, r"^[0-9]+\.[0-9]+"),
+                TokenSpec::new(TokenKind::NumberLiteral, r"^[0-9]+"),
                 // TODO: Support escaping quotes
                 TokenSpec::new(TokenKind::StringLiteral, "^\"[^\"]*\""),
                 TokenSpec::new(TokenKind::StringLiteral, "^'[^']*'"),
             ],
             position: Position::start(),
             source: None,
+            offset: 0,
+            partial: false,
             comments,
+            limits: Limits::default(),
+            tokens: 0,
         }
     }
 
+    /// Whether comments are skipped rather than rejected.
+    pub fn comments(&self) -> bool {
+        self.comments
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
     /// Reset the tokenizers's source related fields to their defaults,
     /// preparing for a new tokenize run.
     fn reset(&mut self) {
         self.position = Position::start();
         self.source = None;
+        self.offset = 0;
+        self.partial = false;
+        self.tokens = 0;
     }
 
-    // TODO: we could make this lazy? but i'm tired
-    pub fn tokenize(&mut self, source: &'source String) -> Result<Vec<Token>, String> {
+    /// Prepares for lazily tokenizing `source` with [`Tokenizer::next_token`].
+    pub fn start(&mut self, source: &'source str) {
         self.reset();
 
         self.source = Some(source);
+    }
+
+    /// Continues lazily tokenizing with `source`, the rest of the input from
+    /// the current position on. Unless `partial` is false, more input may
+    /// follow it, see [`Tokenizer::next_token`].
+    pub fn feed(&mut self, source: &'source str, partial: bool) {
+        self.offset = self.position.cursor();
+        self.source = Some(source);
+        self.partial = partial;
+    }
+
+    /// Drops the source, e.g. to [`Tokenizer::feed`] more input later.
+    pub fn detach(self) -> Tokenizer<'static> {
+        Tokenizer {
+            specs: self.specs,
+            position: self.position,
+            source: None,
+            offset: self.offset,
+            partial: self.partial,
+            comments: self.comments,
+            limits: self.limits,
+            tokens: self.tokens,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// The next significant token, i.e. skipping whitespace and comments.
+    /// Once the source is exhausted, this keeps returning end of input tokens.
+    ///
+    /// For a partial source, this is `None` when more input is needed to tell
+    /// what the next token is. A token is only complete if at least two more
+    /// bytes follow it (e.g. `1.` may become `1.5`), and the rest of the
+    /// source may be the start of one as long as it does not match any token.
+    pub fn next_token(&mut self) -> Result<Option<Token<'source>>, String> {
+        if self.tokens == 0 {
+            self.limits
+                .check_input(self.source.unwrap_or("").as_bytes())?;
+        }
 
-        let mut result = Vec::new();
         while !self.has_reached_end_of_source() {
-            let token = self.get_token()?;
+            let start = self.position;
+            let token = match self.get_token() {
+                Ok(_) if self.partial && self.remaining() < 2 => {
+                    self.position = start;
+                    return Ok(None);
+                }
+                Ok(token) => token,
+                Err(_) if self.partial => return Ok(None),
+                Err(error) => return Err(error),
+            };
 
             // TODO: for now i'm ignoring whitespace stuff here
             //       I thought maybe I could do it in the parser. but eh
@@ -133,37 +211,33 @@ impl<'source> Tokenizer<'source> {
             } else if let TokenKind::LineComment | TokenKind::BlockComment = token.kind() {
                 if !self.comments {
                     // TODO: Perhaps this should some kind of "unrecognized token" stuff?
-                    return Err(format!("comments are not supported"));
+                    return Err("comments are not supported".to_string());
                 }
 
                 continue;
             }
 
-            result.push(token);
+            self.tokens += 1;
+            self.limits.check_token(&token, self.tokens)?;
+
+            return Ok(Some(token));
         }
 
-        // TODO: actually will this ever get reached; I guess if something really weird happened
-        //       i'm not even sure this is the correct logic, but for a placeholder; whatever
-        // verify that we have consumed the full source string; if not return error
-        // if self.position.cursor() < self.source.unwrap().len() {
-        //     return Err("did not consume full source".to_string());
-        // }
+        if self.partial {
+            return Ok(None);
+        }
 
-        // at last push end of input token
-        result.push(Token::new(
+        // at last the end of input token
+        Ok(Some(Token::new(
             TokenKind::Eoi,
             // "<EOI>".to_string(), // or empty string: String::new():
-            String::new(),
+            "",
             Span::collapsed(self.position),
-        ));
-
-        println!("{:#?}", result);
-
-        Ok(result)
+        )))
     }
 
-    fn get_token(&mut self) -> Result<Token, String> {
-        let s = &self.source.unwrap()[self.position.cursor()..].to_string();
+    fn get_token(&mut self) -> Result<Token<'source>, String> {
+        let s = &self.source.unwrap_or("")[self.position.cursor() - self.offset..];
 
         // old position - where the current token starts
         let old_position = self.position;
@@ -171,12 +245,12 @@ impl<'source> Tokenizer<'source> {
         // TODO: This can maybe be done using iterators?
         for spec in &self.specs {
             // TODO: ...or, at least, this maybe?
-            match spec.test(&s) {
+            match spec.test(s) {
                 Some(m) => {
                     // advance the position/cursor
                     match spec.kind {
                         TokenKind::NewLine => self.position.add_line_and_cursor(),
-                        TokenKind::BlockComment => self.position.add_from_str(&m),
+                        TokenKind::BlockComment => self.position.add_from_str(m),
                         _ => self.position.add_columns(m.len()),
                     }
 
@@ -190,17 +264,25 @@ impl<'source> Tokenizer<'source> {
             }
         }
 
-        // FIXME: This error message should be so much better formatted.
-        //        Like; probably get the whole line we're on, if the line is
-        //        much longer than necessary omit leading and trailing based on
-        //        some threshold. Then, kind of like how the Rust compiler does
-        //        it, basically show the line (maybe truncated right) and a line
-        //        below it showing a pointer to the current (self.) position of
-        //        the character.
-        Err(format!("unrecognized token: rest of input was '{}'", s))
+        // only an excerpt, as the rest of the input may be huge
+        let line = s.lines().next().unwrap_or(s);
+        let mut excerpt: String = line.chars().take(EXCERPT_LENGTH).collect();
+        if excerpt.len() < line.len() {
+            excerpt.push_str("...");
+        }
+
+        Err(located(
+            format!("unrecognized token `{}`", excerpt),
+            &Span::collapsed(self.position),
+        ))
     }
 
     fn has_reached_end_of_source(&self) -> bool {
-        self.position.cursor() >= self.source.unwrap().len()
+        self.remaining() == 0
+    }
+
+    /// The number of bytes of the source after the current position.
+    fn remaining(&self) -> usize {
+        self.source.unwrap_or("").len() - (self.position.cursor() - self.offset)
     }
 }
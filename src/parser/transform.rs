@@ -0,0 +1,263 @@
+use super::node::{Node, ObjectNode, Span, StringNode};
+use super::visit::{Path, PathSegment};
+
+/// What to do with a node after it (and its children) were transformed.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Edit {
+    Keep,
+    Replace(Node),
+    Remove,
+}
+
+/// Something to add to a container after its children were transformed.
+/// Insertions that do not fit the container (an item for an object or a
+/// member for an array) are ignored.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Insertion {
+    /// Inserts an array item at the given index, or appends it if the
+    /// index is past the end.
+    Item(usize, Node),
+    /// Adds an object member after all others. If a member with the same key
+    /// exists, only its value is replaced, so it keeps its key and position.
+    Member(String, Node),
+}
+
+/// Hooks called by [`Node::transform`]. Every hook defaults to leaving the
+/// tree as it is.
+#[allow(unused_variables)]
+pub trait Transformer {
+    /// Called for every node, bottom-up: containers are only passed here
+    /// after all of their children were transformed.
+    fn node(&mut self, path: &Path, node: &Node) -> Edit {
+        Edit::Keep
+    }
+
+    /// Called for every object key before the member's value is transformed;
+    /// `path` is the path of the member under its original key. Returning
+    /// `Some` renames the key.
+    fn key(&mut self, path: &Path, key: &str) -> Option<String> {
+        None
+    }
+
+    /// Called for every array and object after its children were transformed.
+    fn insert(&mut self, path: &Path, container: &Node) -> Vec<Insertion> {
+        vec![]
+    }
+}
+
+impl Node {
+    /// Rebuilds this tree according to `transformer`, returning `None` if the
+    /// root itself was removed.
+    ///
+    /// Untouched nodes keep their spans. Containers whose children changed
+    /// get a synthetic span, as their source text no longer matches them, and
+    /// so do nodes created with the synthesizing constructors (e.g.
+    /// [`Node::string`]).
+    pub fn transform<T: Transformer>(self, transformer: &mut T) -> Option<Node> {
+        transform(self, &mut Path::root(), transformer).0
+    }
+}
+
+/// Transforms `node`, also returning whether the result differs from it.
+fn transform<T: Transformer>(
+    node: Node,
+    path: &mut Path,
+    transformer: &mut T,
+) -> (Option<Node>, bool) {
+    let mut changed = false;
+    let mut node = match node {
+        Node::Array(mut array) => {
+            let mut items = Vec::with_capacity(array.value.len());
+
+            for (index, child) in array.value.into_iter().enumerate() {
+                path.push(PathSegment::Index(index));
+                let (child, child_changed) = transform(child, path, transformer);
+                path.pop();
+
+                changed |= child_changed;
+                items.extend(child);
+            }
+            array.value = items;
+
+            let mut node = Node::Array(array);
+            for insertion in transformer.insert(path, &node) {
+                if let (Node::Array(array), Insertion::Item(index, child)) = (&mut node, insertion)
+                {
+                    let index = index.min(array.value.len());
+                    array.value.insert(index, child);
+                    changed = true;
+                }
+            }
+            node
+        }
+        Node::Object(object) => {
+            let mut rebuilt = ObjectNode::empty(object.span);
+
+            // members are transformed in order, so that callbacks are called
+            // in a predictable order
+            for (key_node, child) in object.into_members() {
+                let key = key_node.value.clone();
+                path.push(PathSegment::Key(key.clone()));
+                let renamed = transformer.key(path, &key);
+                let (child, child_changed) = transform(child, path, transformer);
+                path.pop();
+
+                changed |= child_changed || renamed.is_some();
+                let child = match child {
+                    Some(child) => child,
+                    None => continue,
+                };
+
+                let key_node = match &renamed {
+                    Some(renamed) => StringNode::synthetic(renamed),
+                    None => key_node,
+                };
+                rebuilt.insert(key_node, child);
+            }
+
+            let mut node = Node::Object(rebuilt);
+            for insertion in transformer.insert(path, &node) {
+                if let (Node::Object(object), Insertion::Member(key, child)) =
+                    (&mut node, insertion)
+                {
+                    match object.get_mut(&key) {
+                        Some(member) => *member = child,
+                        None => {
+                            object.insert(StringNode::synthetic(&key), child);
+                        }
+                    }
+                    changed = true;
+                }
+            }
+            node
+        }
+        node => node,
+    };
+
+    if changed {
+        match &mut node {
+            Node::Array(array) => array.span = Span::synthetic(),
+            Node::Object(object) => object.span = Span::synthetic(),
+            _ => {}
+        }
+    }
+
+    match transformer.node(path, &node) {
+        Edit::Keep => (Some(node), changed),
+        Edit::Replace(replacement) => (Some(replacement), true),
+        Edit::Remove => (None, true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::node::NumberNodeValue;
+    use crate::parser::parse_source;
+
+    struct Redact;
+
+    impl Transformer for Redact {
+        fn node(&mut self, path: &Path, node: &Node) -> Edit {
+            match (path.pointer().as_str(), node) {
+                ("/password", _) => Edit::Replace(Node::string("***")),
+                ("/debug", _) => Edit::Remove,
+                (_, Node::Number(number)) => match number.value {
+                    NumberNodeValue::Float(f) => Edit::Replace(Node::int(f as i64)),
+                    NumberNodeValue::Int(_) => Edit::Keep,
+                },
+                _ => Edit::Keep,
+            }
+        }
+
+        fn key(&mut self, path: &Path, key: &str) -> Option<String> {
+            if path.depth() == 1 && key == "user" {
+                Some("username".to_string())
+            } else {
+                None
+            }
+        }
+
+        fn insert(&mut self, path: &Path, container: &Node) -> Vec<Insertion> {
+            match container {
+                Node::Object(_) if path.depth() == 0 => {
+                    vec![Insertion::Member(
+                        "redacted".to_string(),
+                        Node::boolean(true),
+                    )]
+                }
+                Node::Array(_) => vec![Insertion::Item(0, Node::null())],
+                _ => vec![],
+            }
+        }
+    }
+
+    #[test]
+    fn it_transforms_trees() {
+        let root = parse_source(
+            "{\"user\": \"me\", \"password\": \"secret\", \"debug\": true, \"limits\": [1, 2.5]}",
+        );
+        let original_user_span = *root.pointer("/user").unwrap().span();
+
+        let root = root.transform(&mut Redact).unwrap();
+
+        assert_eq!(
+            root.raw(),
+            "{\"username\":\"me\",\"password\":\"***\",\"limits\":[null,1,2],\"redacted\":true}"
+        );
+        assert!(root.span().is_synthetic());
+        assert!(root.pointer("/limits").unwrap().span().is_synthetic());
+        assert_eq!(
+            root.pointer("/username").unwrap().span(),
+            &original_user_span
+        );
+        assert!(root.pointer("/password").unwrap().span().is_synthetic());
+        assert!(root.pointer("/limits/0").unwrap().span().is_synthetic());
+    }
+
+    #[test]
+    fn it_inserts_only_what_fits() {
+        struct Insert;
+        impl Transformer for Insert {
+            fn insert(&mut self, _: &Path, container: &Node) -> Vec<Insertion> {
+                match container {
+                    Node::Object(_) => vec![
+                        Insertion::Item(0, Node::null()),
+                        Insertion::Member("c".to_string(), Node::int(3)),
+                        Insertion::Member("a".to_string(), Node::float(1e21)),
+                    ],
+                    _ => vec![Insertion::Member("x".to_string(), Node::null())],
+                }
+            }
+        }
+
+        let key_span = |root: &Node| match root {
+            Node::Object(object) => object.key("a").map(|key| key.span),
+            _ => None,
+        };
+
+        let source = "{\"a\": 1, \"b\": [ true ]}";
+        let root = parse_source(source);
+        let original_key_span = key_span(&root);
+        let root = root.transform(&mut Insert).unwrap();
+
+        assert_eq!(
+            root.raw(),
+            "{\"a\":1000000000000000000000.0,\"b\":[true],\"c\":3}"
+        );
+        assert_eq!(root.raw_in(source), None);
+        assert_eq!(root.pointer("/b").unwrap().raw_in(source), Some("[ true ]"));
+        assert_eq!(key_span(&root), original_key_span);
+        assert!(!original_key_span.unwrap().is_synthetic());
+    }
+
+    #[test]
+    fn it_keeps_untouched_trees() {
+        struct Identity;
+        impl Transformer for Identity {}
+
+        let source = "{\"a\": [1, {\"b\": null}]}";
+        let root = parse_source(source);
+        assert_eq!(root.clone().transform(&mut Identity), Some(root));
+    }
+}
@@ -0,0 +1,110 @@
+use std::convert::TryFrom;
+
+use serde_json::{Map, Number, Value};
+
+use super::node::{Node, NumberNodeValue};
+
+impl From<&Node> for Value {
+    /// Object members are inserted in source order, so with the
+    /// `preserve_order` feature the resulting map keeps that order.
+    /// Non-finite floats (which can only appear in synthesized nodes) become
+    /// `null`, just like serde_json itself does.
+    fn from(node: &Node) -> Value {
+        match node {
+            Node::Null(_) => Value::Null,
+            Node::Boolean(node) => Value::Bool(node.value),
+            Node::Number(node) => match node.value {
+                NumberNodeValue::Int(value) => Value::Number(value.into()),
+                NumberNodeValue::Float(value) => {
+                    Number::from_f64(value).map_or(Value::Null, Value::Number)
+                }
+            },
+            Node::String(node) => Value::String(node.value.clone()),
+            Node::Array(array) => Value::Array(array.value.iter().map(Value::from).collect()),
+            Node::Object(object) => Value::Object(
+                object
+                    .entries()
+                    .map(|(key, node)| (key.clone(), Value::from(node)))
+                    .collect::<Map<_, _>>(),
+            ),
+        }
+    }
+}
+
+impl From<Node> for Value {
+    fn from(node: Node) -> Value {
+        Value::from(&node)
+    }
+}
+
+impl TryFrom<Value> for Node {
+    type Error = String;
+
+    /// The resulting nodes are synthesized. This fails for integers that do
+    /// not fit into an `i64`, since they can not be represented losslessly.
+    fn try_from(value: Value) -> Result<Node, String> {
+        Ok(match value {
+            Value::Null => Node::null(),
+            Value::Bool(value) => Node::boolean(value),
+            Value::Number(number) => match (number.as_i64(), number.as_f64()) {
+                (Some(value), _) => Node::int(value),
+                // `as_f64` also converts (lossily) integers out of range
+                (None, Some(value)) if number.is_f64() => Node::float(value),
+                _ => return Err(format!("number `{}` is out of range", number)),
+            },
+            Value::String(value) => Node::string(&value),
+            Value::Array(items) => Node::array(
+                items
+                    .into_iter()
+                    .map(Node::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Object(map) => Node::object(
+                map.into_iter()
+                    .map(|(key, value)| Ok((key, Node::try_from(value)?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn it_converts_nodes_to_values() {
+        let node = Parser::new_without_comments()
+            .parse_str("{\"b\": [1, 2.5, \"s\"], \"a\": {\"c\": null, \"d\": true}}")
+            .unwrap();
+
+        let value = Value::from(&node);
+        assert_eq!(
+            value,
+            json!({"b": [1, 2.5, "s"], "a": {"c": null, "d": true}})
+        );
+        assert!(value["b"][0].is_i64());
+        assert!(value["b"][1].is_f64());
+
+        #[cfg(feature = "preserve_order")]
+        assert_eq!(
+            value.as_object().unwrap().keys().collect::<Vec<_>>(),
+            vec!["b", "a"]
+        );
+    }
+
+    #[test]
+    fn it_converts_values_to_nodes() {
+        let value = json!({"a": [1, -2, 0.5, "x", false, null]});
+
+        let node = Node::try_from(value.clone()).unwrap();
+        assert!(node.span().is_synthetic());
+        assert_eq!(node.raw(), "{\"a\":[1,-2,0.5,\"x\",false,null]}");
+        assert_eq!(Value::from(node), value);
+
+        assert!(Node::try_from(json!(u64::MAX)).is_err());
+    }
+}
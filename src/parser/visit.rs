@@ -0,0 +1,281 @@
+use std::fmt;
+
+use super::node::{ArrayNode, BooleanNode, Node, NullNode, NumberNode, ObjectNode, StringNode};
+use super::pointer;
+
+/// A single step from a container to one of its children.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// The location of a node relative to the root of the tree being walked.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+pub struct Path {
+    segments: Vec<PathSegment>,
+}
+
+impl Path {
+    pub fn root() -> Path {
+        Path { segments: vec![] }
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    pub fn depth(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn push(&mut self, segment: PathSegment) {
+        self.segments.push(segment);
+    }
+
+    pub fn pop(&mut self) -> Option<PathSegment> {
+        self.segments.pop()
+    }
+
+    /// A copy of this path extended by one segment.
+    pub fn child(&self, segment: PathSegment) -> Path {
+        let mut path = self.clone();
+        path.push(segment);
+        path
+    }
+
+    /// The JSON Pointer (RFC 6901) of this path, e.g. `/key6/2`.
+    pub fn pointer(&self) -> String {
+        pointer::format(self.segments.iter().map(|segment| match segment {
+            PathSegment::Key(key) => key.clone(),
+            PathSegment::Index(index) => index.to_string(),
+        }))
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pointer())
+    }
+}
+
+/// Tells the walker how to continue after visiting a node.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Control {
+    Continue,
+    /// Do not descend into the children of the current container. Its
+    /// `leave_*` hook is still called. Same as `Continue` for scalars.
+    SkipChildren,
+    /// Stop walking altogether; no further hooks are called.
+    Stop,
+}
+
+/// Per node type hooks called by [`Node::accept`] in depth-first order.
+/// Every hook defaults to doing nothing and continuing the walk.
+#[allow(unused_variables)]
+pub trait Visitor<'a> {
+    fn visit_null(&mut self, path: &Path, node: &'a NullNode) -> Control {
+        Control::Continue
+    }
+
+    fn visit_boolean(&mut self, path: &Path, node: &'a BooleanNode) -> Control {
+        Control::Continue
+    }
+
+    fn visit_number(&mut self, path: &Path, node: &'a NumberNode) -> Control {
+        Control::Continue
+    }
+
+    fn visit_string(&mut self, path: &Path, node: &'a StringNode) -> Control {
+        Control::Continue
+    }
+
+    fn enter_array(&mut self, path: &Path, node: &'a ArrayNode) -> Control {
+        Control::Continue
+    }
+
+    fn leave_array(&mut self, path: &Path, node: &'a ArrayNode) -> Control {
+        Control::Continue
+    }
+
+    fn enter_object(&mut self, path: &Path, node: &'a ObjectNode) -> Control {
+        Control::Continue
+    }
+
+    fn leave_object(&mut self, path: &Path, node: &'a ObjectNode) -> Control {
+        Control::Continue
+    }
+}
+
+impl Node {
+    /// Walks this tree depth-first, calling the matching hooks of `visitor`.
+    /// Object members are visited in source order. Returns `false` if the
+    /// walk was stopped early by a hook returning [`Control::Stop`].
+    pub fn accept<'a, V: Visitor<'a>>(&'a self, visitor: &mut V) -> bool {
+        accept(self, &mut Path::root(), visitor) != Control::Stop
+    }
+
+    /// A depth-first (pre-order) iterator over `(path, node)` pairs of this
+    /// tree, starting with the root itself.
+    pub fn walk(&self) -> Walk<'_> {
+        Walk {
+            stack: vec![(Path::root(), self)],
+            pending: None,
+        }
+    }
+}
+
+fn accept<'a, V: Visitor<'a>>(node: &'a Node, path: &mut Path, visitor: &mut V) -> Control {
+    match node {
+        Node::Null(node) => visitor.visit_null(path, node),
+        Node::Boolean(node) => visitor.visit_boolean(path, node),
+        Node::Number(node) => visitor.visit_number(path, node),
+        Node::String(node) => visitor.visit_string(path, node),
+        Node::Array(array) => {
+            match visitor.enter_array(path, array) {
+                Control::Stop => return Control::Stop,
+                Control::SkipChildren => {}
+                Control::Continue => {
+                    for (index, child) in array.value.iter().enumerate() {
+                        path.push(PathSegment::Index(index));
+                        let control = accept(child, path, visitor);
+                        path.pop();
+                        if control == Control::Stop {
+                            return Control::Stop;
+                        }
+                    }
+                }
+            }
+            visitor.leave_array(path, array)
+        }
+        Node::Object(object) => {
+            match visitor.enter_object(path, object) {
+                Control::Stop => return Control::Stop,
+                Control::SkipChildren => {}
+                Control::Continue => {
+                    for (key, child) in object.entries() {
+                        path.push(PathSegment::Key(key.clone()));
+                        let control = accept(child, path, visitor);
+                        path.pop();
+                        if control == Control::Stop {
+                            return Control::Stop;
+                        }
+                    }
+                }
+            }
+            visitor.leave_object(path, object)
+        }
+    }
+}
+
+/// Iterator returned by [`Node::walk`].
+pub struct Walk<'a> {
+    stack: Vec<(Path, &'a Node)>,
+    /// The last yielded node, whose children are only pushed onto the stack
+    /// on the next call, so that [`Walk::skip_subtree`] can still prevent it.
+    pending: Option<(Path, &'a Node)>,
+}
+
+impl<'a> Walk<'a> {
+    /// Skips the children of the node that was yielded last.
+    pub fn skip_subtree(&mut self) {
+        self.pending = None;
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = (Path, &'a Node);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((path, node)) = self.pending.take() {
+            // children are pushed in reverse so that they are popped in order
+            match node {
+                Node::Array(array) => {
+                    for (index, child) in array.value.iter().enumerate().rev() {
+                        self.stack
+                            .push((path.child(PathSegment::Index(index)), child));
+                    }
+                }
+                Node::Object(object) => {
+                    for (key, child) in object.entries().rev() {
+                        self.stack
+                            .push((path.child(PathSegment::Key(key.clone())), child));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let (path, node) = self.stack.pop()?;
+        if node.is_array() || node.is_object() {
+            self.pending = Some((path.clone(), node));
+        }
+
+        Some((path, node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    #[derive(Default)]
+    struct Collector {
+        events: Vec<String>,
+    }
+
+    impl<'a> Visitor<'a> for Collector {
+        fn visit_number(&mut self, path: &Path, node: &'a NumberNode) -> Control {
+            self.events.push(format!("{}={}", path, node.raw));
+            if node.raw == "3" {
+                Control::Stop
+            } else {
+                Control::Continue
+            }
+        }
+
+        fn enter_object(&mut self, path: &Path, _: &'a ObjectNode) -> Control {
+            self.events.push(format!("enter {}", path));
+            if path.depth() > 0 {
+                Control::SkipChildren
+            } else {
+                Control::Continue
+            }
+        }
+
+        fn leave_object(&mut self, path: &Path, _: &'a ObjectNode) -> Control {
+            self.events.push(format!("leave {}", path));
+            Control::Continue
+        }
+    }
+
+    #[test]
+    fn it_visits_in_source_order() {
+        let root = parse_source("{\"b\": 1, \"a\": {\"c\": 2}, \"d\": [3, 4], \"e\": 5}");
+
+        let mut collector = Collector::default();
+        assert!(!root.accept(&mut collector));
+        assert_eq!(
+            collector.events,
+            vec!["enter ", "/b=1", "enter /a", "leave /a", "/d/0=3"]
+        );
+    }
+
+    #[test]
+    fn it_walks_and_skips_subtrees() {
+        let root = parse_source("[{\"a\": [1]}, [2], 3]");
+
+        let mut walk = root.walk();
+        let mut pointers = vec![];
+        while let Some((path, node)) = walk.next() {
+            pointers.push(path.pointer());
+            if path.pointer() == "/1" {
+                assert!(node.is_array());
+                walk.skip_subtree();
+            }
+        }
+
+        assert_eq!(pointers, vec!["", "/0", "/0/a", "/0/a/0", "/1", "/2"]);
+    }
+}